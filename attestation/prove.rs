@@ -1,30 +1,280 @@
 use clap::Parser;
+use std::time::Duration;
 
-use tlsnprover::{config::AppConfig, domain, utils::info};
+use tlsnprover::{
+    config::AppConfig,
+    domain::{self, Provider},
+    utils::{self, info, text_parser},
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    info::init_tracing().expect("Failed to initialize tracing");
-
     let args = domain::ProveArgs::parse();
     let app_config =
         AppConfig::new().map_err(|e| format!("Failed to load configuration: {}", e))?;
+    info::init_tracing(
+        args.output.verbosity(),
+        args.output.plain_output || app_config.plain_output,
+    )
+    .expect("Failed to initialize tracing");
+    utils::run_until_interrupted(run(args, app_config)).await
+}
+
+async fn run(
+    args: domain::ProveArgs,
+    app_config: AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let notary = app_config
+        .resolve_notary(app_config.notary_environment)
+        .map_err(|e| format!("Failed to resolve notary: {}", e))?;
+
+    let provider = match (&args.provider, &args.url) {
+        (Some(provider), _) => provider.clone(),
+        (None, Some(url)) => text_parser::parse_provider_from_url(url),
+        (None, None) => Provider::Wise,
+    };
+    let server = app_config.server_config(&provider);
+
+    // Needs only the transaction the secrets were saved under, not a full endpoint
+    if args.mode == domain::Mode::Cleanup {
+        let transaction_id = args.transaction_id.clone().or_else(|| {
+            args.url
+                .as_deref()
+                .and_then(text_parser::url_transaction_id)
+        });
+        shred_secrets(&provider, transaction_id.as_deref()).await?;
+        return Ok(());
+    }
+
+    let url = match (&args.url, &args.transaction_id) {
+        (Some(url), _) => Some(url.clone()),
+        (None, Some(transaction_id)) => {
+            let profile_id = args.profile_ids.first().map(String::as_str);
+            Some(provider.transaction_endpoint(profile_id, transaction_id)?)
+        }
+        (None, None) => None,
+    };
+    // Artifacts are saved under the transaction the URL names
+    let transaction_id = url.as_deref().and_then(text_parser::url_transaction_id);
+
+    if args.mode == domain::Mode::Verify {
+        let mut options =
+            app_config.verify_options(args.max_age.map(Duration::from_secs), args.offline);
+        options.expected_transaction_id = args.transaction_id.clone();
+        options.expected_nonce = args.nonce.clone();
+        tlsnprover::verify_artifact(
+            url.as_deref(),
+            args.bundle.as_deref(),
+            &app_config.unauthed_bytes,
+            args.format,
+            &options,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if let Some(path) = &args.fixture {
+        let fixture = std::fs::read(path)
+            .map_err(|e| format!("Failed to read fixture {}: {}", path.display(), e))?;
+        tlsnprover::prove_fixture(&provider, server, &fixture)?;
+        return Ok(());
+    }
+
+    let cookie = args
+        .cookie()
+        .map_err(|e| format!("Failed to read cookie file: {}", e))?;
+    let access_token = args
+        .access_token()
+        .map_err(|e| format!("Failed to read access token file: {}", e))?;
+
+    let mut headers = args.headers.clone();
+    if let Some(nonce) = &args.nonce {
+        let header = server
+            .nonce_header
+            .as_deref()
+            .filter(|_| server.nonce_echo_pattern.is_some())
+            .ok_or("--nonce needs nonce_header and nonce_echo_pattern for the provider")?;
+        headers.push((header.to_string(), nonce.clone()));
+    }
+
+    let provider_config = domain::ProviderConfig::new(
+        provider.clone(),
+        cookie.clone().unwrap_or_default(),
+        access_token.clone().unwrap_or_default(),
+    )
+    .with_extra_headers(&headers);
+    let user_agent = app_config.user_agent_for(&provider);
+
+    if args.check_credentials
+        && !utils::check_credentials(&provider_config, server, user_agent).await?
+    {
+        return Err(format!("{} rejected the provided credentials", provider).into());
+    }
+
+    if args.mode == domain::Mode::ProveToPresent && !args.force {
+        // The saved attestation may be for any of the candidate profiles; checking each
+        // locally avoids asking the provider which one holds the transaction
+        let candidates = match (&args.url, &args.transaction_id) {
+            (None, Some(transaction_id)) if args.profile_ids.len() > 1 => args
+                .profile_ids
+                .iter()
+                .map(|profile_id| provider.transaction_endpoint(Some(profile_id), transaction_id))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => url.iter().cloned().collect(),
+        };
+        for candidate in &candidates {
+            let resumed = tlsnprover::resume_presentation(
+                &provider,
+                server,
+                candidate,
+                &args.reveal_fields,
+                args.format,
+            )
+            .await?;
+            if resumed {
+                return cleanup_after_present(&args, &provider, transaction_id.as_deref()).await;
+            }
+        }
+    }
+
+    // With several candidate profiles, find the one holding the transaction before
+    // notarizing
+    let url = match &args.transaction_id {
+        Some(transaction_id)
+            if args.profile_ids.len() > 1 && args.mode != domain::Mode::Present =>
+        {
+            let profile_id = utils::find_profile_id(
+                &provider_config,
+                server,
+                user_agent,
+                &args.profile_ids,
+                transaction_id,
+            )
+            .await?;
+            Some(provider.transaction_endpoint(Some(&profile_id), transaction_id)?)
+        }
+        _ => url,
+    };
 
-    tlsnprover::prove(
+    if let Some(timeout) = args
+        .wait_for_settlement
+        .filter(|_| args.mode != domain::Mode::Present)
+    {
+        let url = url
+            .as_deref()
+            .ok_or("--wait-for-settlement needs --url or --transaction-id")?;
+        utils::wait_for_settlement(
+            &provider_config,
+            server,
+            user_agent,
+            url,
+            Duration::from_secs(timeout),
+        )
+        .await?;
+    }
+
+    let notary_client = tlsnprover::connect_notary(
         &args.mode,
-        args.url.as_deref(),
-        args.cookie.as_deref(),
-        args.access_token.as_deref(),
-        &app_config.user_agent,
-        &app_config.wise.host,
-        app_config.wise.port,
-        &app_config.notary.server.host,
-        app_config.notary.server.port,
-        app_config.notary.tls_enabled,
-        app_config.max_sent_data,
-        app_config.max_recv_data,
+        &notary.server.host,
+        notary.server.port,
+        notary.tls_enabled,
+        notary.path_prefix.as_deref(),
+        user_agent,
     )
     .await?;
 
+    let options = tlsnprover::ProveOptions {
+        url: url.as_deref(),
+        cookie: cookie.as_deref(),
+        access_token: access_token.as_deref(),
+        extra_headers: &headers,
+        connect_timeout: Duration::from_secs(app_config.connect_timeout),
+        read_timeout: Duration::from_secs(app_config.read_timeout),
+        commit_strategy: args.commit_strategy.unwrap_or(app_config.commit_strategy),
+        format: args.format,
+        reveal_fields: &args.reveal_fields,
+        confirm_commit: args
+            .preview_commits
+            .then_some(&confirm_commit as &info::ConfirmCommit),
+        ..tlsnprover::ProveOptions::new(
+            user_agent,
+            args.max_sent.unwrap_or(app_config.max_sent_data),
+            args.max_recv.unwrap_or(app_config.max_recv_data),
+        )
+    };
+    // The full server config carries per-provider settings such as the HTTP version
+    tlsnprover::prove_with_notary(&args.mode, &notary_client, server, &options).await?;
+
+    cleanup_after_present(&args, &provider, transaction_id.as_deref()).await
+}
+
+/// Prints the commit preview and, on an interactive terminal, asks whether to notarize
+fn confirm_commit(ranges: &[info::CommitRange]) -> bool {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    info::print_commit_preview(ranges);
+    if !std::io::stdin().is_terminal() {
+        return true;
+    }
+
+    eprint!("Notarize these ranges? [y/N] ");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).is_ok()
+        && matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+async fn cleanup_after_present(
+    args: &domain::ProveArgs,
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let presented = matches!(
+        args.mode,
+        domain::Mode::Present | domain::Mode::ProveToPresent
+    );
+    if args.stdout && presented {
+        print_presentation(provider, transaction_id)?;
+    }
+    if args.cleanup_secrets && presented {
+        shred_secrets(provider, transaction_id).await?;
+    }
+    Ok(())
+}
+
+/// Writes the saved presentation file, header included, to stdout as one base64 line
+fn print_presentation(
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use base64::Engine;
+    use std::io::Write;
+
+    let path = utils::file_io::get_transaction_file_path(
+        &provider.to_string(),
+        transaction_id,
+        "presentation",
+    );
+    let bytes =
+        std::fs::read(&path).map_err(|e| format!("Failed to read presentation {}: {}", path, e))?;
+    let mut stdout = std::io::stdout().lock();
+    writeln!(
+        stdout,
+        "{}",
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+async fn shred_secrets(
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if utils::file_io::shred_secrets(provider, transaction_id).await? {
+        tracing::info!("Secrets overwritten and removed");
+    } else {
+        tracing::info!("No secrets file to remove");
+    }
     Ok(())
 }
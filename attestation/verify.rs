@@ -1,16 +1,84 @@
 use clap::Parser;
+use std::time::Duration;
 
-use tlsnprover::{config::AppConfig, domain::VerifyArgs, utils::info};
+use tlsnprover::{
+    config::AppConfig,
+    domain::{self, VerifyArgs},
+    utils::{self, info},
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    info::init_tracing().expect("Failed to initialize tracing");
-
     let args = VerifyArgs::parse();
     let app_config =
         AppConfig::new().map_err(|e| format!("Failed to load configuration: {}", e))?;
+    info::init_tracing(
+        args.output.verbosity(),
+        args.output.plain_output || app_config.plain_output,
+    )
+    .expect("Failed to initialize tracing");
+    utils::run_until_interrupted(run(args, app_config)).await
+}
+
+async fn run(args: VerifyArgs, app_config: AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut options =
+        app_config.verify_options(args.max_age.map(Duration::from_secs), args.offline);
+    options.expected_transaction_id = args.expected_transaction_id.clone();
+    options.expected_nonce = args.expected_nonce.clone();
+
+    if let Some(provider) = &args.provider {
+        let results = tlsnprover::verify_batch(
+            provider,
+            &args.transaction_ids,
+            &app_config.unauthed_bytes,
+            args.format,
+            &options,
+        )
+        .await;
+        info::print_batch_summary(&args.transaction_ids, &results);
+
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        if failed > 0 {
+            return Err(format!("{} of {} presentations failed", failed, results.len()).into());
+        }
+        return Ok(());
+    }
+
+    let report = match &args.presentation {
+        Some(path) => {
+            let bytes = std::fs::read(path)
+                .map_err(|e| format!("Failed to read presentation {}: {}", path.display(), e))?;
+            tlsnprover::verify_bytes(
+                None,
+                &bytes,
+                &app_config.unauthed_bytes,
+                args.format,
+                &options,
+            )?
+        }
+        None => {
+            tlsnprover::verify_artifact(
+                args.url.as_deref(),
+                args.bundle.as_deref(),
+                &app_config.unauthed_bytes,
+                args.format,
+                &options,
+            )
+            .await?
+        }
+    };
 
-    tlsnprover::verify(&args.url, &app_config.unauthed_bytes).await?;
+    if let Some(path) = &args.receipt {
+        let signing_key = app_config
+            .receipt_signing_key
+            .as_deref()
+            .ok_or("--receipt needs receipt_signing_key to be configured")?;
+        let receipt =
+            domain::sign_verification_report(&report, &domain::parse_signing_key(signing_key)?)?;
+        std::fs::write(path, serde_json::to_vec_pretty(&receipt)?)
+            .map_err(|e| format!("Failed to write receipt {}: {}", path.display(), e))?;
+        tracing::info!("Signed receipt written to {}", path.display());
+    }
 
     Ok(())
 }
@@ -19,10 +19,18 @@ fn generate_c_headers() {
         .with_include_guard("TLSNPROVER_H")
         .include_item("tlsn_init")
         .include_item("tlsn_cleanup")
+        .include_item("TlsnTokenRefreshCallback")
+        .include_item("tlsn_set_token_refresh_callback")
         .include_item("tlsn_prove")
         .include_item("tlsn_verify")
+        .include_item("tlsn_warmup")
+        .include_item("tlsn_version")
+        .include_item("tlsn_supported_providers")
+        .include_item("tlsn_last_output_paths")
+        .include_item("tlsn_check_credentials")
         .include_item("tlsn_get_last_error")
         .include_item("tlsn_free_error_string")
+        .include_item("tlsn_free_string")
         .generate()
         .expect("Unable to generate bindings")
         .write_to_file(header_path);
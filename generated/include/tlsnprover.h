@@ -13,16 +13,33 @@
 
 namespace tlsnprover {
 
+/// Returns a fresh access token, or null if none could be obtained.
+/// The string stays owned by the host and is copied before the callback returns.
+using TlsnTokenRefreshCallback = const char*(*)();
+
 extern "C" {
 
 int32_t tlsn_init();
 
 void tlsn_cleanup();
 
+/// Registers a callback invoked once when the provider rejects the access token with
+/// a 401 during `tlsn_prove`. Pass null to clear it.
+void tlsn_set_token_refresh_callback(TlsnTokenRefreshCallback callback);
+
+/// Runs a prove/present session.
+///
+/// Artifacts are saved under the transaction `url` names, so present and verify need
+/// the same `url` to find them. `extra_headers` are sent with every provider request,
+/// as newline-separated `Name: value` lines; null sends none.
+///
+/// Calls are serialized: a second thread calling `tlsn_prove` or `tlsn_verify` blocks
+/// until the running session finishes, since both read and write the same artifact files.
 int32_t tlsn_prove(int32_t mode,
                    const char *url,
                    const char *cookie,
                    const char *access_token,
+                   const char *extra_headers,
                    const char *user_agent,
                    const char *provider_host,
                    uint16_t provider_port,
@@ -32,12 +49,55 @@ int32_t tlsn_prove(int32_t mode,
                    uintptr_t max_sent_data,
                    uintptr_t max_recv_data);
 
-int32_t tlsn_verify(const char *url, const char *unauthed_bytes);
+/// Verifies the saved presentation for the transaction `url` names, or the unkeyed one
+/// when it names none. Serialized with `tlsn_prove`.
+///
+/// `trusted_notary_keys` and `allowed_server_names` are newline-separated lists. Null
+/// or empty keys accept any notary; null or empty server names accept only the hosts
+/// of the supported providers' endpoints.
+int32_t tlsn_verify(const char *url,
+                    const char *unauthed_bytes,
+                    const char *trusted_notary_keys,
+                    const char *allowed_server_names);
+
+/// Resolves the provider host and opens and drops a TCP connection to it, so the first
+/// `tlsn_prove` skips cold DNS and connection setup. Sends no request and notarizes
+/// nothing; safe to call while the user is still entering details.
+int32_t tlsn_warmup(const char *provider_host, uint16_t provider_port);
+
+/// Returns the library version, e.g. `0.1.0`.
+/// The string is static: it stays valid for the process lifetime and must not be freed.
+const char *tlsn_version();
 
+/// Returns a JSON array describing the supported providers.
+/// The returned string must be released with `tlsn_free_string`.
+const char *tlsn_supported_providers();
+
+/// Returns a JSON array of the files written by the most recent `tlsn_prove` call, in
+/// write order. Files are named by provider and transaction; prove-to-present for Wise
+/// transaction 123456789 lists `wise.123456789.attestation.tlsn`, then the `secrets`,
+/// `certificate`, `presentation` and `bundle` files named the same way. A failed call
+/// lists whatever it wrote before failing.
+/// The returned string must be released with `tlsn_free_string`.
+const char *tlsn_last_output_paths();
+
+/// Returns the most recent error message set on the calling thread, or null if none
+/// is set. Release with `tlsn_free_error_string`.
 const char *tlsn_get_last_error();
 
+/// Checks provider credentials without notarizing.
+/// On success, `valid` is set to whether the provider accepted them.
+int32_t tlsn_check_credentials(const char *provider_host,
+                               uint16_t provider_port,
+                               const char *cookie,
+                               const char *access_token,
+                               const char *user_agent,
+                               bool *valid);
+
 void tlsn_free_error_string(char *ptr);
 
+void tlsn_free_string(char *ptr);
+
 }  // extern "C"
 
 }  // namespace tlsnprover
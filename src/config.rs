@@ -1,18 +1,79 @@
+use clap::ValueEnum;
 use config::{Config, ConfigError, File};
 use serde::Deserialize;
 use std::env;
+use std::time::Duration;
 
-use crate::domain::{NotaryConfig, ServerConfig};
+use crate::domain::{
+    CommitStrategy, NotaryConfig, NotaryEnvironment, Provider, ServerConfig, VerifyOptions,
+    ZelleBank,
+};
+
+const PRODUCTION_NOTARY_HOST: &str = "notary.pse.dev";
+const PRODUCTION_NOTARY_PORT: u16 = 7047;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub user_agent: String,
     pub max_sent_data: usize,
     pub max_recv_data: usize,
+    #[serde(default)]
+    pub commit_strategy: CommitStrategy,
+    /// Seconds allowed to connect to the provider
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Seconds allowed to receive the provider's response
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout: u64,
     pub paypal: ServerConfig,
     pub wise: ServerConfig,
+    pub revolut: ServerConfig,
+    pub mercadopago: ServerConfig,
+    pub cashapp: ServerConfig,
+    pub zelle_chase: ServerConfig,
     pub notary: NotaryConfig,
+    #[serde(default)]
+    pub notary_environment: NotaryEnvironment,
+    pub notary_staging: Option<NotaryConfig>,
+    pub notary_production: Option<NotaryConfig>,
     pub unauthed_bytes: String,
+    /// Hex-encoded notary verifying keys accepted by verify; empty accepts any notary
+    #[serde(default)]
+    pub trusted_notary_keys: Vec<String>,
+    /// Server names verify accepts; empty falls back to the configured provider hosts
+    #[serde(default)]
+    pub allowed_server_names: Vec<String>,
+    /// Request headers whose values are masked in logs, matched case-insensitively
+    #[serde(default = "default_redacted_headers")]
+    pub redacted_headers: Vec<String>,
+    /// Widest gap of JSON punctuation bridged between committed field ranges
+    #[serde(default)]
+    pub range_merge_gap: usize,
+    /// Log in plain ASCII, without emoji or colors
+    #[serde(default)]
+    pub plain_output: bool,
+    /// DNS-over-HTTPS JSON endpoint for resolving hosts; system DNS when unset or failing
+    #[serde(default)]
+    pub doh_url: Option<String>,
+    /// Hex secp256k1 secret key verify signs receipts with; best supplied through
+    /// `ZKP2P_RECEIPT_SIGNING_KEY` rather than a config file
+    #[serde(default)]
+    pub receipt_signing_key: Option<String>,
+}
+
+fn default_redacted_headers() -> Vec<String> {
+    crate::utils::tls::DEFAULT_REDACTED_HEADERS
+        .iter()
+        .map(|header| header.to_string())
+        .collect()
+}
+
+fn default_connect_timeout() -> u64 {
+    crate::utils::net::DEFAULT_CONNECT_TIMEOUT.as_secs()
+}
+
+fn default_read_timeout() -> u64 {
+    crate::utils::net::DEFAULT_READ_TIMEOUT.as_secs()
 }
 
 impl AppConfig {
@@ -25,27 +86,444 @@ impl AppConfig {
             s = s.add_source(File::with_name(&format!("config/{}", env)).required(false));
         }
 
-        s.build()?.try_deserialize()
+        Self::from_builder(s)
     }
 
+    /// Builds and validates a config from exactly the given sources
+    fn from_builder(
+        builder: config::ConfigBuilder<config::builder::DefaultState>,
+    ) -> Result<Self, ConfigError> {
+        let mut app_config: AppConfig = builder.build()?.try_deserialize()?;
+        app_config.validate()?;
+        app_config.share_server_settings();
+
+        Ok(app_config)
+    }
+
+    /// Checks every setting and reports all problems at once rather than the first
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(ConfigError::Message(format!(
+            "{} configuration problem(s):\n  - {}",
+            problems.len(),
+            problems.join("\n  - ")
+        )))
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.user_agent.trim().is_empty() {
+            problems.push("user_agent must not be empty".to_string());
+        }
+        if self.max_sent_data == 0 {
+            problems.push("max_sent_data must be positive".to_string());
+        }
+        if self.max_recv_data < crate::MIN_RECV_DATA {
+            problems.push(format!(
+                "max_recv_data must be at least {} bytes",
+                crate::MIN_RECV_DATA
+            ));
+        }
+        if self.connect_timeout == 0 {
+            problems.push("connect_timeout must be positive".to_string());
+        }
+        if self.read_timeout == 0 {
+            problems.push("read_timeout must be positive".to_string());
+        }
+        if let Some(doh_url) = &self.doh_url {
+            let valid = doh_url
+                .parse::<hyper::Uri>()
+                .is_ok_and(|uri| uri.scheme_str() == Some("https") && uri.host().is_some());
+            if !valid {
+                problems.push(format!("doh_url must be an https URL, got {:?}", doh_url));
+            }
+        }
+        // The error never echoes the key itself
+        let invalid_key = self
+            .receipt_signing_key
+            .as_deref()
+            .is_some_and(|key| crate::domain::parse_signing_key(key).is_err());
+        if invalid_key {
+            problems.push(
+                "receipt_signing_key must be a hex-encoded 32-byte secp256k1 secret key"
+                    .to_string(),
+            );
+        }
+        if self.unauthed_bytes.len() != 1 {
+            problems.push(format!(
+                "unauthed_bytes must be a single ASCII character, got {:?}",
+                self.unauthed_bytes
+            ));
+        }
+
+        let notaries = [
+            ("notary", Some(&self.notary)),
+            ("notary_staging", self.notary_staging.as_ref()),
+            ("notary_production", self.notary_production.as_ref()),
+        ];
+        for (name, notary) in notaries {
+            let path_prefix = notary.and_then(|notary| notary.path_prefix.as_deref());
+            if let Some(path_prefix) = path_prefix.filter(|prefix| {
+                prefix
+                    .chars()
+                    .any(|c| c.is_whitespace() || matches!(c, '?' | '#'))
+            }) {
+                problems.push(format!(
+                    "[{}] path_prefix must be a plain URL path, got {:?}",
+                    name, path_prefix
+                ));
+            }
+        }
+        let servers = [
+            ("wise", &self.wise),
+            ("paypal", &self.paypal),
+            ("revolut", &self.revolut),
+            ("mercadopago", &self.mercadopago),
+            ("cashapp", &self.cashapp),
+            ("zelle_chase", &self.zelle_chase),
+        ]
+        .into_iter()
+        .chain(
+            notaries
+                .into_iter()
+                .filter_map(|(name, notary)| notary.map(|notary| (name, &notary.server))),
+        );
+        for (name, server) in servers {
+            if let Err(e) = server.validate() {
+                problems.push(format!("[{}] {}", name, e));
+            }
+        }
+
+        problems
+    }
+
+    /// Copies the top-level settings each provider server carries into every one of them
+    fn share_server_settings(&mut self) {
+        let (redacted_headers, doh_url, range_merge_gap) = (
+            self.redacted_headers.clone(),
+            self.doh_url.clone(),
+            self.range_merge_gap,
+        );
+        for server in [
+            &mut self.wise,
+            &mut self.paypal,
+            &mut self.revolut,
+            &mut self.mercadopago,
+            &mut self.cashapp,
+            &mut self.zelle_chase,
+        ] {
+            server.redacted_headers = redacted_headers.clone();
+            server.doh_url = doh_url.clone();
+            server.range_merge_gap = range_merge_gap;
+        }
+    }
+
+    pub fn server_config(&self, provider: &Provider) -> &ServerConfig {
+        match provider {
+            Provider::Wise => &self.wise,
+            Provider::PayPal => &self.paypal,
+            Provider::Revolut => &self.revolut,
+            Provider::MercadoPago => &self.mercadopago,
+            Provider::CashApp => &self.cashapp,
+            Provider::Zelle(ZelleBank::Chase) => &self.zelle_chase,
+        }
+    }
+
+    /// Server config of every supported provider
+    pub fn provider_servers(&self) -> Vec<&ServerConfig> {
+        Provider::value_variants()
+            .iter()
+            .map(|provider| self.server_config(provider))
+            .collect()
+    }
+
+    /// Server names a verified presentation may be for: `allowed_server_names` when set,
+    /// otherwise every configured provider host and SNI name
+    pub fn allowed_server_names(&self) -> Vec<String> {
+        if !self.allowed_server_names.is_empty() {
+            return self.allowed_server_names.clone();
+        }
+
+        let mut names = Vec::new();
+        for server in self.provider_servers() {
+            names.push(server.host.clone());
+            names.extend(server.sni_name.clone());
+        }
+        names
+    }
+
+    /// Verification policy from the config plus the per-run age and offline settings
+    pub fn verify_options(&self, max_age: Option<Duration>, offline: bool) -> VerifyOptions {
+        VerifyOptions {
+            max_age,
+            offline,
+            trusted_notary_keys: self.trusted_notary_keys.clone(),
+            allowed_server_names: self.allowed_server_names(),
+            expected_transaction_id: None,
+            expected_nonce: None,
+            settled_states: Provider::value_variants()
+                .iter()
+                .map(|provider| {
+                    let states = self.server_config(provider).settled_states.clone();
+                    (provider.clone(), states)
+                })
+                .collect(),
+            nonce_echo_patterns: Provider::value_variants()
+                .iter()
+                .filter_map(|provider| {
+                    let pattern = self.server_config(provider).nonce_echo_pattern.clone()?;
+                    Some((provider.clone(), pattern))
+                })
+                .collect(),
+        }
+    }
+
+    /// User-Agent for the provider, falling back to the global one
+    pub fn user_agent_for(&self, provider: &Provider) -> &str {
+        self.server_config(provider)
+            .user_agent
+            .as_deref()
+            .unwrap_or(&self.user_agent)
+    }
+
+    /// Resolves the notary for the given environment.
+    ///
+    /// `Local` uses the `[notary]` section, `Staging` requires `[notary_staging]` and
+    /// `Production` falls back to the public PSE notary. `NOTARY_HOST`, `NOTARY_PORT`,
+    /// `NOTARY_TLS` and `NOTARY_PATH_PREFIX` override the resolved values when set.
+    pub fn resolve_notary(
+        &self,
+        environment: NotaryEnvironment,
+    ) -> Result<NotaryConfig, ConfigError> {
+        let mut notary = match environment {
+            NotaryEnvironment::Local => self.notary.clone(),
+            NotaryEnvironment::Staging => self
+                .notary_staging
+                .clone()
+                .ok_or_else(|| ConfigError::NotFound("notary_staging".to_string()))?,
+            NotaryEnvironment::Production => {
+                self.notary_production
+                    .clone()
+                    .unwrap_or_else(|| NotaryConfig {
+                        server: ServerConfig::new(PRODUCTION_NOTARY_HOST, PRODUCTION_NOTARY_PORT),
+                        tls_enabled: true,
+                        path_prefix: None,
+                    })
+            }
+        };
+
+        if let Ok(host) = env::var("NOTARY_HOST") {
+            notary.server.host = host;
+        }
+        if let Ok(port) = env::var("NOTARY_PORT") {
+            notary.server.port = port.parse().map_err(|e| {
+                ConfigError::Message(format!("Invalid NOTARY_PORT {:?}: {}", port, e))
+            })?;
+        }
+        if let Ok(tls) = env::var("NOTARY_TLS") {
+            notary.tls_enabled = tls.parse().map_err(|e| {
+                ConfigError::Message(format!("Invalid NOTARY_TLS {:?}: {}", tls, e))
+            })?;
+        }
+        if let Ok(path_prefix) = env::var("NOTARY_PATH_PREFIX") {
+            notary.path_prefix = Some(path_prefix);
+        }
+
+        Ok(notary)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::normalize_path_prefix;
+
+    /// The shipped default config alone, so `ZKP2P_*` variables in the developer's
+    /// environment can't change test results
+    fn default_config() -> AppConfig {
+        AppConfig::from_builder(Config::builder().add_source(File::from_str(
+            include_str!("../config/default.toml"),
+            config::FileFormat::Toml,
+        )))
+        .unwrap()
+    }
 
     #[test]
     fn test_config_loading() {
-        let app_config = AppConfig::new().unwrap();
+        let app_config = default_config();
         assert_eq!(app_config.wise.host, "wise.com");
         assert_eq!(app_config.wise.port, 443);
 
         assert_eq!(app_config.paypal.host, "www.paypal.com");
         assert_eq!(app_config.paypal.port, 443);
 
+        assert_eq!(app_config.revolut.host, "app.revolut.com");
+        assert_eq!(app_config.revolut.port, 443);
+
+        assert_eq!(app_config.mercadopago.host, "api.mercadopago.com");
+        assert_eq!(
+            app_config.mercadopago.settled_states,
+            vec!["approved".to_string()]
+        );
+        assert_eq!(app_config.cashapp.host, "cash.app");
+
         let notary_config = app_config.notary.clone();
         assert_eq!(notary_config.server.host, "127.0.0.1");
         assert_eq!(notary_config.server.port, 7047);
-        assert_eq!(notary_config.tls_enabled, false);
+        assert!(!notary_config.tls_enabled);
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut app_config = default_config();
+        assert!(app_config.validate().is_ok());
+
+        app_config.max_sent_data = 0;
+        app_config.unauthed_bytes = String::new();
+        app_config.wise.host = " ".to_string();
+        app_config.notary.server.port = 0;
+
+        let message = app_config.validate().unwrap_err().to_string();
+        assert!(message.starts_with("4 configuration problem(s)"));
+        assert!(message.contains("max_sent_data"));
+        assert!(message.contains("unauthed_bytes"));
+        assert!(message.contains("[wise] host must not be empty"));
+        assert!(message.contains("[notary] port"));
+    }
+
+    #[test]
+    fn test_doh_url_must_be_https() {
+        let mut app_config = default_config();
+        app_config.doh_url = Some("https://1.1.1.1/dns-query".to_string());
+        assert!(app_config.validate().is_ok());
+
+        app_config.doh_url = Some("http://1.1.1.1/dns-query".to_string());
+        assert!(
+            app_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("doh_url")
+        );
+    }
+
+    #[test]
+    fn test_notary_path_prefix() {
+        let mut app_config = default_config();
+        app_config.notary.path_prefix = Some("/notary/v1/".to_string());
+        assert!(app_config.validate().is_ok());
+        assert_eq!(
+            normalize_path_prefix(app_config.notary.path_prefix.as_deref()),
+            Some("notary/v1")
+        );
+        assert_eq!(normalize_path_prefix(Some("/")), None);
+
+        app_config.notary.path_prefix = Some("/notary?v=1".to_string());
+        assert!(
+            app_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("[notary] path_prefix")
+        );
+    }
+
+    #[test]
+    fn test_allowed_server_names_default_to_provider_hosts() {
+        let mut app_config = default_config();
+        app_config.allowed_server_names.clear();
+        let names = app_config.allowed_server_names();
+        assert!(names.contains(&"wise.com".to_string()));
+        assert!(names.contains(&"www.paypal.com".to_string()));
+
+        app_config.allowed_server_names = vec!["example.com".to_string()];
+        assert_eq!(app_config.allowed_server_names(), vec!["example.com"]);
+    }
+
+    #[test]
+    fn test_tls_constraints_must_allow_mpc_tls() {
+        use crate::domain::TlsVersion;
+
+        let mut app_config = default_config();
+        app_config.wise.tls_versions = vec![TlsVersion::Tls13];
+        assert!(app_config.wise.validate().is_err());
+
+        app_config.wise.tls_versions = vec![TlsVersion::Tls12, TlsVersion::Tls13];
+        assert!(app_config.wise.validate().is_err());
+
+        app_config.wise.tls_versions = vec![TlsVersion::Tls12];
+        app_config.wise.cipher_suites = vec![
+            "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string(),
+            "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384".to_string(),
+        ];
+        assert!(app_config.wise.validate().is_err());
+
+        app_config.wise.cipher_suites = vec!["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string()];
+        assert!(app_config.wise.validate().is_ok());
+    }
+
+    #[test]
+    fn test_top_level_settings_shared_with_servers() {
+        let app_config = AppConfig::from_builder(
+            Config::builder()
+                .add_source(File::from_str(
+                    include_str!("../config/default.toml"),
+                    config::FileFormat::Toml,
+                ))
+                .set_override("range_merge_gap", 4)
+                .unwrap()
+                .set_override("doh_url", "https://1.1.1.1/dns-query")
+                .unwrap(),
+        )
+        .unwrap();
+
+        for server in app_config.provider_servers() {
+            assert_eq!(server.range_merge_gap, 4);
+            assert_eq!(server.doh_url.as_deref(), Some("https://1.1.1.1/dns-query"));
+            assert_eq!(server.redacted_headers, app_config.redacted_headers);
+        }
+    }
+
+    #[test]
+    fn test_user_agent_override() {
+        let mut app_config = default_config();
+        assert_eq!(
+            app_config.user_agent_for(&Provider::Wise),
+            app_config.user_agent
+        );
+
+        app_config.paypal.user_agent = Some("PayPal/8.0 (iPhone)".to_string());
+        assert_eq!(
+            app_config.user_agent_for(&Provider::PayPal),
+            "PayPal/8.0 (iPhone)"
+        );
+        assert_eq!(
+            app_config.user_agent_for(&Provider::Wise),
+            app_config.user_agent
+        );
+
+        app_config.paypal.user_agent = Some(" ".to_string());
+        assert!(app_config.paypal.validate().is_err());
+    }
+
+    #[test]
+    fn test_resolve_notary_environments() {
+        let app_config = default_config();
+
+        let production = app_config
+            .resolve_notary(NotaryEnvironment::Production)
+            .unwrap();
+        assert_eq!(production.server.host, PRODUCTION_NOTARY_HOST);
+        assert!(production.tls_enabled);
+
+        assert!(matches!(
+            app_config.resolve_notary(NotaryEnvironment::Staging),
+            Err(ConfigError::NotFound(_))
+        ));
     }
 }
@@ -1,17 +1,91 @@
-use clap::{Parser, ValueEnum};
+use clap::builder::PossibleValue;
+use clap::{Args, Parser, ValueEnum};
+use serde::Deserialize;
 use std::fmt;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, ValueEnum, PartialEq)]
 pub enum Mode {
     Prove,
     Present,
     ProveToPresent,
+    /// Verify a saved presentation or bundle
+    Verify,
+    /// Prove, present and verify against the built-in fixture server
+    SelfTest,
+    /// Overwrite and delete the secrets file once its presentation exists
+    Cleanup,
 }
 
-#[derive(Debug, Clone, ValueEnum, PartialEq)]
+/// What the prover commits to before notarization
+#[derive(Debug, Clone, Copy, ValueEnum, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommitStrategy {
+    /// Only the Host header and the extracted payment fields
+    #[default]
+    Fields,
+    /// Every parsed HTTP component, for a richer but larger attestation
+    FullHttp,
+}
+
+/// Serialization used for saved attestations, secrets and presentations
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Default)]
+pub enum ArtifactFormat {
+    /// Compact binary encoding
+    #[default]
+    Bincode,
+    /// Human-readable JSON, easier to inspect and diff
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Provider {
     Wise,
     PayPal,
+    Revolut,
+    MercadoPago,
+    CashApp,
+    /// Zelle has no API of its own; payments are proven through the sending bank's portal
+    Zelle(ZelleBank),
+}
+
+/// Bank portal a Zelle payment is proven through. Each bank brings its own host,
+/// endpoint template and field patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ZelleBank {
+    Chase,
+}
+
+impl ZelleBank {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZelleBank::Chase => "chase",
+        }
+    }
+}
+
+impl ValueEnum for Provider {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Provider::Wise,
+            Provider::PayPal,
+            Provider::Revolut,
+            Provider::MercadoPago,
+            Provider::CashApp,
+            Provider::Zelle(ZelleBank::Chase),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Provider::Wise => "wise",
+            Provider::PayPal => "paypal",
+            Provider::Revolut => "revolut",
+            Provider::MercadoPago => "mercadopago",
+            Provider::CashApp => "cashapp",
+            Provider::Zelle(ZelleBank::Chase) => "zelle-chase",
+        }))
+    }
 }
 
 impl fmt::Display for Provider {
@@ -19,6 +93,57 @@ impl fmt::Display for Provider {
         match self {
             Provider::Wise => write!(f, "wise"),
             Provider::PayPal => write!(f, "paypal"),
+            Provider::Revolut => write!(f, "revolut"),
+            Provider::MercadoPago => write!(f, "mercadopago"),
+            Provider::CashApp => write!(f, "cashapp"),
+            Provider::Zelle(bank) => write!(f, "zelle-{}", bank.name()),
+        }
+    }
+}
+
+/// How much the binaries log, independent of where the logs go
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Verbosity {
+    /// Warnings and errors only
+    Quiet,
+    /// `RUST_LOG` when set, otherwise info
+    #[default]
+    Normal,
+    /// Debug output from every module
+    Verbose,
+}
+
+impl Verbosity {
+    /// Filter directive for the level, or `None` to defer to `RUST_LOG`
+    pub fn filter(&self) -> Option<&'static str> {
+        match self {
+            Verbosity::Quiet => Some("warn"),
+            Verbosity::Normal => None,
+            Verbosity::Verbose => Some("debug"),
+        }
+    }
+}
+
+/// Output flags shared by the prove and verify binaries
+#[derive(Args, Debug, Clone, Default)]
+pub struct OutputArgs {
+    /// Only log warnings and errors; overrides RUST_LOG
+    #[clap(long, short, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Log debug details; overrides RUST_LOG
+    #[clap(long, short)]
+    pub verbose: bool,
+    /// Plain ASCII logs without emoji or colors; also set by `plain_output` in the config
+    #[clap(long)]
+    pub plain_output: bool,
+}
+
+impl OutputArgs {
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, true) => Verbosity::Verbose,
+            (false, false) => Verbosity::Normal,
         }
     }
 }
@@ -29,33 +154,229 @@ pub struct ProveArgs {
     /// Operation mode
     #[clap(long, value_enum)]
     pub mode: Mode,
-    /// API endpoint URL
-    #[clap(
-        long,
-        required_if_eq("mode", "prove"),
-        required_if_eq("mode", "prove_to_present")
-    )]
+    /// Payment provider; inferred from the URL when omitted
+    #[clap(long, value_enum)]
+    pub provider: Option<Provider>,
+    /// API endpoint URL; a `{profile_id}` placeholder is resolved automatically
+    #[clap(long, conflicts_with = "transaction_id")]
     pub url: Option<String>,
-    /// Session cookie
+    /// Transaction id used to build the provider endpoint instead of `--url`
+    #[clap(long)]
+    pub transaction_id: Option<String>,
+    /// Profile id for providers whose endpoint is scoped to a profile. Several
+    /// comma-separated ids are tried in order until one holds the transaction.
     #[clap(
-        long,
-        required_if_eq("mode", "prove"),
-        required_if_eq("mode", "prove_to_present")
+        long = "profile-id",
+        value_delimiter = ',',
+        requires = "transaction_id"
     )]
+    pub profile_ids: Vec<String>,
+    /// Presentation bundle to verify in verify mode
+    #[clap(long, conflicts_with_all = ["url", "transaction_id"])]
+    pub bundle: Option<String>,
+    /// Reject presentations whose TLS session is older than this many seconds (verify mode)
+    #[clap(long)]
+    pub max_age: Option<u64>,
+    /// Skip wall-clock checks such as --max-age (verify mode)
+    #[clap(long)]
+    pub offline: bool,
+    /// Serialization format for saved artifacts
+    #[clap(long, value_enum, default_value_t)]
+    pub format: ArtifactFormat,
+    /// Check the cookie and access token with a plain request before notarizing
+    #[clap(long)]
+    pub check_credentials: bool,
+    /// Poll the transaction with plain requests for up to this many seconds until it
+    /// reaches one of the provider's `settled_states`, then notarize it
+    #[clap(long)]
+    pub wait_for_settlement: Option<u64>,
+    /// Run the commit and reveal logic on a saved raw request+response file and log
+    /// the ranges instead of notarizing; needs no credentials or network
+    #[clap(long, conflicts_with = "bundle")]
+    pub fixture: Option<PathBuf>,
+    /// Challenge to send in the provider's `nonce_header`; the server's echo of it,
+    /// located by `nonce_echo_pattern`, is committed and revealed
+    #[clap(long)]
+    pub nonce: Option<String>,
+    /// Show every byte range about to be committed and ask before notarizing; without
+    /// a terminal on stdin the ranges are only logged
+    #[clap(long)]
+    pub preview_commits: bool,
+    /// Also print the saved presentation, base64-encoded, to stdout once it is built
+    /// (present and prove-to-present modes). Logs stay on stderr.
+    #[clap(long)]
+    pub stdout: bool,
+    /// Comma-separated fields to present instead of the provider's default set, e.g.
+    /// to also disclose Wise's opt-in `sourceAmount,sourceCurrency,fee,rate`
+    #[clap(long, value_delimiter = ',')]
+    pub reveal_fields: Vec<String>,
+    /// Overwrite and delete the secrets file once the presentation is built in present
+    /// mode; prove-to-present always removes them once its presentation is saved
+    #[clap(long)]
+    pub cleanup_secrets: bool,
+    /// Re-notarize in prove-to-present mode even when a saved attestation for the
+    /// transaction exists
+    #[clap(long)]
+    pub force: bool,
+    /// Commit strategy; overrides `commit_strategy` from the config
+    #[clap(long, value_enum)]
+    pub commit_strategy: Option<CommitStrategy>,
+    /// Maximum bytes sent to the provider; overrides `max_sent_data` from the config
+    #[clap(long)]
+    pub max_sent: Option<usize>,
+    /// Maximum bytes received from the provider; overrides `max_recv_data` from the config
+    #[clap(long)]
+    pub max_recv: Option<usize>,
+    /// Session cookie; required when proving with a cookie-based provider.
+    /// Prefer --cookie-file or the env var, which stay out of shell history
+    #[clap(long, env = "ZKP2P_COOKIE", hide_env_values = true)]
     pub cookie: Option<String>,
-    /// Access token
-    #[clap(
-        long,
-        required_if_eq("mode", "prove"),
-        required_if_eq("mode", "prove_to_present")
-    )]
+    /// File holding the session cookie; takes precedence over --cookie
+    #[clap(long)]
+    pub cookie_file: Option<PathBuf>,
+    /// Access token; required when proving with a token-based provider
+    #[clap(long, env = "ZKP2P_ACCESS_TOKEN", hide_env_values = true)]
     pub access_token: Option<String>,
+    /// File holding the access token; takes precedence over --access-token
+    #[clap(long)]
+    pub access_token_file: Option<PathBuf>,
+    /// Extra request header as `Name: value`, e.g. `X-Device-Id: abc`; repeatable.
+    /// Values of headers in `redacted_headers` are masked in logs
+    #[clap(long = "header", value_parser = crate::domain::parse_header_line)]
+    pub headers: Vec<(String, String)>,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl ProveArgs {
+    /// Session cookie from --cookie-file, --cookie or `ZKP2P_COOKIE`, in that order
+    pub fn cookie(&self) -> std::io::Result<Option<String>> {
+        read_secret(self.cookie_file.as_deref(), self.cookie.as_deref())
+    }
+
+    /// Access token from --access-token-file, --access-token or `ZKP2P_ACCESS_TOKEN`
+    pub fn access_token(&self) -> std::io::Result<Option<String>> {
+        read_secret(
+            self.access_token_file.as_deref(),
+            self.access_token.as_deref(),
+        )
+    }
+}
+
+/// Reads a secret from `file` when given, else takes `value`, dropping the trailing
+/// newline editors and `echo` leave behind
+fn read_secret(file: Option<&Path>, value: Option<&str>) -> std::io::Result<Option<String>> {
+    let secret = match (file, value) {
+        (Some(path), _) => std::fs::read_to_string(path)?,
+        (None, Some(value)) => value.to_string(),
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(secret.trim_end_matches(['\r', '\n']).to_string()))
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about = "ZKP2P TLSNotary Verifier - Verifying")]
 pub struct VerifyArgs {
     /// API endpoint URL
+    #[clap(
+        long,
+        required_unless_present_any = ["bundle", "presentation", "transaction_ids"]
+    )]
+    pub url: Option<String>,
+    /// Presentation file to verify, its provider detected from the proven server name
+    #[clap(long, conflicts_with_all = ["url", "bundle", "transaction_ids"])]
+    pub presentation: Option<PathBuf>,
+    /// Presentation bundle to verify instead of the provider's presentation file
+    #[clap(long, conflicts_with = "url")]
+    pub bundle: Option<String>,
+    /// Comma-separated transaction ids whose saved presentations are verified as a batch
+    #[clap(long, value_delimiter = ',', conflicts_with_all = ["url", "bundle"], requires = "provider")]
+    pub transaction_ids: Vec<String>,
+    /// Provider of the batch's presentations
+    #[clap(long, value_enum, requires = "transaction_ids")]
+    pub provider: Option<Provider>,
+    /// Reject presentations whose TLS session is older than this many seconds
     #[clap(long)]
-    pub url: String,
+    pub max_age: Option<u64>,
+    /// Skip wall-clock checks such as --max-age for reproducible verification
+    #[clap(long)]
+    pub offline: bool,
+    /// Reject the presentation unless its revealed request is for this transaction
+    #[clap(long, conflicts_with = "transaction_ids")]
+    pub expected_transaction_id: Option<String>,
+    /// Reject the presentation unless it reveals the server echoing this nonce
+    #[clap(long, conflicts_with = "transaction_ids")]
+    pub expected_nonce: Option<String>,
+    /// Write a receipt of the verified result, signed with `receipt_signing_key`
+    #[clap(long, conflicts_with = "transaction_ids")]
+    pub receipt: Option<PathBuf>,
+    /// Serialization format of the artifact being verified
+    #[clap(long, value_enum, default_value_t)]
+    pub format: ArtifactFormat,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_file_takes_precedence_and_is_trimmed() {
+        let path = std::env::temp_dir().join(format!("tlsn-secret-{}", std::process::id()));
+        std::fs::write(&path, "session=abc\r\n").unwrap();
+
+        let args = ProveArgs::parse_from([
+            "tlsn-prove",
+            "--mode",
+            "prove",
+            "--cookie",
+            "session=from-flag",
+            "--cookie-file",
+            path.to_str().unwrap(),
+            "--access-token",
+            "token\n",
+        ]);
+        assert_eq!(args.cookie().unwrap().as_deref(), Some("session=abc"));
+        assert_eq!(args.access_token().unwrap().as_deref(), Some("token"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(args.cookie().is_err());
+    }
+
+    #[test]
+    fn test_output_flags_select_verbosity() {
+        let args = ProveArgs::parse_from(["tlsn-prove", "--mode", "prove"]);
+        assert_eq!(args.output.verbosity(), Verbosity::Normal);
+        assert_eq!(args.output.verbosity().filter(), None);
+
+        let args = ProveArgs::parse_from(["tlsn-prove", "--mode", "prove", "-q"]);
+        assert_eq!(args.output.verbosity().filter(), Some("warn"));
+
+        let args = VerifyArgs::parse_from(["tlsn-verify", "--url", "https://wise.com/", "-v"]);
+        assert_eq!(args.output.verbosity(), Verbosity::Verbose);
+
+        assert!(ProveArgs::try_parse_from(["tlsn-prove", "--mode", "prove", "-q", "-v"]).is_err());
+    }
+
+    #[test]
+    fn test_profile_ids_accept_a_list() {
+        let args = ProveArgs::parse_from([
+            "tlsn-prove",
+            "--mode",
+            "prove",
+            "--transaction-id",
+            "987654321",
+            "--profile-id",
+            "111,222",
+            "--profile-id",
+            "333",
+        ]);
+        assert_eq!(args.profile_ids, ["111", "222", "333"]);
+
+        assert!(
+            ProveArgs::try_parse_from(["tlsn-prove", "--mode", "prove", "--profile-id", "111"])
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tlsn_core::presentation::Presentation;
+
+/// A presentation packaged with the metadata needed to submit it as a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresentationBundle {
+    pub provider: String,
+    pub transaction_id: Option<String>,
+    /// Hex-encoded verifying key of the notary that signed the attestation
+    pub notary_key: String,
+    /// Time of the notarized TLS session
+    pub session_time: DateTime<Utc>,
+    /// Leaf certificate the server presented, when the prover could inspect it
+    pub certificate: Option<CertificateInfo>,
+    pub presentation: Presentation,
+}
+
+/// Identity details of a server's leaf certificate.
+///
+/// The MPC-TLS prover does not expose the chain it validated, so these come from a
+/// handshake the prover makes with the same server just before notarizing. They are
+/// not covered by the notary's signature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CertificateInfo {
+    /// Subject distinguished name, e.g. `CN=wise.com`
+    pub subject: String,
+    /// Issuer distinguished name, e.g. `C=US, O=Let's Encrypt, CN=R3`
+    pub issuer: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// Base64 SHA-256 of the public key, as `openssl dgst -sha256 -binary | base64` prints it
+    pub spki_sha256: String,
+}
+
+impl CertificateInfo {
+    pub fn valid_at(&self, time: DateTime<Utc>) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+}
@@ -1,9 +1,17 @@
 pub mod args;
+pub mod bundle;
+pub mod currency;
 pub mod providers;
+pub mod receipt;
 pub mod server;
 pub mod transaction;
+pub mod verification;
 
 pub use args::*;
+pub use bundle::*;
+pub use currency::*;
 pub use providers::*;
+pub use receipt::*;
 pub use server::*;
 pub use transaction::*;
+pub use verification::*;
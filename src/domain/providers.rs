@@ -1,29 +1,614 @@
+use clap::ValueEnum;
+use serde::Serialize;
 use tracing::debug;
 
-use crate::domain::Provider;
+use crate::domain::{Provider, ZelleBank};
+use crate::error::ZkpError;
+
+/// Shape of the authentication headers a provider expects
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthScheme {
+    /// Session `Cookie` plus an `X-Access-Token` header
+    CookieAndToken,
+    /// Session `Cookie` only
+    Cookie,
+    /// `Authorization: Bearer <access_token>`
+    Bearer,
+    /// Fixed list of header name/value pairs
+    Custom(Vec<(String, String)>),
+    /// No credentials; for public endpoints
+    Anonymous,
+}
+
+impl AuthScheme {
+    /// Whether the scheme sends the session cookie and the access token, respectively
+    pub fn required_credentials(&self) -> (bool, bool) {
+        match self {
+            AuthScheme::CookieAndToken => (true, true),
+            AuthScheme::Cookie => (true, false),
+            AuthScheme::Bearer => (false, true),
+            AuthScheme::Custom(_) | AuthScheme::Anonymous => (false, false),
+        }
+    }
+}
+
+impl Provider {
+    pub fn auth_scheme(&self) -> AuthScheme {
+        match self {
+            Provider::Wise => AuthScheme::CookieAndToken,
+            Provider::PayPal => AuthScheme::Cookie,
+            Provider::Revolut => AuthScheme::Cookie,
+            Provider::MercadoPago => AuthScheme::Bearer,
+            Provider::CashApp => AuthScheme::Cookie,
+            Provider::Zelle(ZelleBank::Chase) => AuthScheme::Cookie,
+        }
+    }
+
+    pub fn endpoint_template(&self) -> &'static str {
+        match self {
+            Provider::Wise => {
+                "https://wise.com/gateway/v3/profiles/{profile_id}/transfers/{transaction_id}"
+            }
+            Provider::PayPal => {
+                "https://www.paypal.com/myaccount/activities/details/inline/{transaction_id}"
+            }
+            Provider::Revolut => "https://app.revolut.com/api/retail/transaction/{transaction_id}",
+            Provider::MercadoPago => "https://api.mercadopago.com/v1/payments/{transaction_id}",
+            Provider::CashApp => "https://cash.app/api/v1/activity/{transaction_id}",
+            Provider::Zelle(ZelleBank::Chase) => {
+                "https://secure.chase.com/svc/rr/payments/secure/v1/quickpay/payment/activity/detail/{transaction_id}"
+            }
+        }
+    }
+
+    /// Host of the provider's endpoint template
+    pub fn endpoint_host(&self) -> &'static str {
+        let template = self.endpoint_template();
+        let host = template
+            .split_once("://")
+            .map_or(template, |(_, rest)| rest);
+        host.split(['/', ':', '?']).next().unwrap_or(host)
+    }
+
+    /// Endpoint hosts of every supported provider, the server names a presentation
+    /// may be for when no allowlist is configured
+    pub fn endpoint_hosts() -> Vec<String> {
+        Provider::value_variants()
+            .iter()
+            .map(|provider| provider.endpoint_host().to_string())
+            .collect()
+    }
+
+    /// Registrable domain the provider's API is served under
+    pub fn domain(&self) -> &'static str {
+        match self {
+            Provider::Wise => "wise.com",
+            Provider::PayPal => "paypal.com",
+            Provider::Revolut => "revolut.com",
+            Provider::MercadoPago => "mercadopago.com",
+            Provider::CashApp => "cash.app",
+            Provider::Zelle(ZelleBank::Chase) => "chase.com",
+        }
+    }
+
+    /// Provider whose domain is `server_name` or a parent of it, e.g. `www.paypal.com`
+    /// maps to PayPal. Unlike `parse_provider_from_url` there is no fallback.
+    pub fn from_server_name(server_name: &str) -> Option<Provider> {
+        let server_name = server_name.trim_end_matches('.').to_ascii_lowercase();
+        Provider::value_variants()
+            .iter()
+            .find(|provider| {
+                let domain = provider.domain();
+                server_name == domain || server_name.ends_with(&format!(".{}", domain))
+            })
+            .cloned()
+    }
+
+    /// Picks the provider whose patterns apply to a presentation for `server_name`.
+    ///
+    /// A `given` provider must agree with the one detected from the server name. When
+    /// nothing is detected it is only used for a server in `allowed_server_names` or a
+    /// provider endpoint host, since a configured host may differ from the provider's
+    /// public domain but an arbitrary server must not borrow its patterns.
+    pub fn resolve(
+        given: Option<&Provider>,
+        server_name: &str,
+        allowed_server_names: &[String],
+    ) -> Result<Provider, ZkpError> {
+        match (given, Provider::from_server_name(server_name)) {
+            (Some(given), Some(detected)) if *given != detected => {
+                Err(ZkpError::ProviderMismatch {
+                    given: given.to_string(),
+                    detected: detected.to_string(),
+                })
+            }
+            (Some(given), Some(_)) => Ok(given.clone()),
+            (Some(given), None) => {
+                let known = allowed_server_names
+                    .iter()
+                    .chain(&Provider::endpoint_hosts())
+                    .any(|allowed| allowed.eq_ignore_ascii_case(server_name));
+                if known {
+                    Ok(given.clone())
+                } else {
+                    Err(ZkpError::UnknownProvider(server_name.to_string()))
+                }
+            }
+            (None, Some(detected)) => Ok(detected),
+            (None, None) => Err(ZkpError::UnknownProvider(server_name.to_string())),
+        }
+    }
+
+    /// Cookie the provider's web app keeps the access token in, if any. Credentials are
+    /// normalized through it so either one alone is enough to send both.
+    pub fn token_cookie(&self) -> Option<&'static str> {
+        match self {
+            Provider::Wise => Some("appToken"),
+            Provider::PayPal
+            | Provider::Revolut
+            | Provider::MercadoPago
+            | Provider::CashApp
+            | Provider::Zelle(_) => None,
+        }
+    }
+
+    /// Lightweight authenticated path used to check credentials before notarizing
+    pub fn credential_check_path(&self) -> &'static str {
+        match self {
+            Provider::Wise => "/gateway/v1/profiles",
+            Provider::PayPal => "/myaccount/summary",
+            Provider::Revolut => "/api/retail/user/current",
+            Provider::MercadoPago => "/users/me",
+            Provider::CashApp => "/api/v1/profile",
+            Provider::Zelle(ZelleBank::Chase) => "/svc/rr/profile/secure/v1/customer/list",
+        }
+    }
+}
+
+/// One request in a provider's notarized flow
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestStep {
+    /// Requests carry no body, so only body-less methods make sense here
+    pub method: hyper::Method,
+    /// URL template using the same placeholders as the provider's endpoint template
+    pub endpoint_template: &'static str,
+    /// Status the response must have before the next step is sent
+    pub required_status: u16,
+    /// Whether payment fields are committed from this step's response
+    pub commit_fields: bool,
+}
+
+impl RequestStep {
+    /// A GET of the transaction endpoint whose fields are committed
+    pub const fn transaction(endpoint_template: &'static str) -> Self {
+        RequestStep {
+            method: hyper::Method::GET,
+            endpoint_template,
+            required_status: 200,
+            commit_fields: true,
+        }
+    }
+
+    /// URL for this step, with placeholders filled from the transaction URL the flow
+    /// was started with
+    pub fn resolve(&self, provider: &Provider, transaction_url: &str) -> Result<String, String> {
+        if self.endpoint_template == provider.endpoint_template() {
+            return Ok(transaction_url.to_string());
+        }
+
+        let values =
+            template_values(provider.endpoint_template(), transaction_url).ok_or_else(|| {
+                format!(
+                    "{:?} does not match the {} endpoint template",
+                    transaction_url, provider
+                )
+            })?;
+        let endpoint = values.iter().fold(
+            self.endpoint_template.to_string(),
+            |endpoint, (name, value)| endpoint.replace(&format!("{{{}}}", name), value),
+        );
+        if endpoint.contains('{') {
+            return Err(format!("Unresolved placeholder in {:?}", endpoint));
+        }
+
+        endpoint
+            .parse::<hyper::Uri>()
+            .map_err(|e| format!("Invalid {} endpoint {:?}: {}", provider, endpoint, e))?;
+        Ok(endpoint)
+    }
+}
+
+/// Placeholder values in `url`, matched against the literal text of `template`
+fn template_values(template: &str, url: &str) -> Option<Vec<(String, String)>> {
+    let mut values = Vec::new();
+    let mut template = template;
+    let mut rest = url;
+
+    while let Some(open) = template.find('{') {
+        rest = rest.strip_prefix(&template[..open])?;
+        let close = open + template[open..].find('}')?;
+        let name = &template[open + 1..close];
+        template = &template[close + 1..];
+
+        let literal = &template[..template.find('{').unwrap_or(template.len())];
+        let value_len = if literal.is_empty() {
+            rest.len()
+        } else {
+            rest.find(literal)?
+        };
+        values.push((name.to_string(), rest[..value_len].to_string()));
+        rest = &rest[value_len..];
+    }
+
+    (rest == template).then_some(values)
+}
+
+impl Provider {
+    /// Requests sent over one notarized connection, in order. Every provider currently
+    /// needs a single request; longer flows (e.g. list then details) add steps here.
+    pub fn request_steps(&self) -> Vec<RequestStep> {
+        vec![RequestStep::transaction(self.endpoint_template())]
+    }
+
+    /// Fills the endpoint template with the given identifiers.
+    ///
+    /// Templates may carry a query string; substituted values are percent-encoded so
+    /// reserved characters can't break the request line, and the result must parse as a URI.
+    pub fn transaction_endpoint(
+        &self,
+        profile_id: Option<&str>,
+        transaction_id: &str,
+    ) -> Result<String, String> {
+        let template = self.endpoint_template();
+        if profile_id.is_none() && template.contains("{profile_id}") {
+            return Err(format!("{} endpoints require a profile id", self));
+        }
+
+        let mut endpoint = template.replace("{transaction_id}", &percent_encode(transaction_id));
+        if let Some(profile_id) = profile_id {
+            endpoint = endpoint.replace("{profile_id}", &percent_encode(profile_id));
+        }
+
+        endpoint
+            .parse::<hyper::Uri>()
+            .map_err(|e| format!("Invalid {} endpoint {:?}: {}", self, endpoint, e))?;
+        Ok(endpoint)
+    }
+}
+
+/// Percent-encodes everything outside the RFC 3986 unreserved set
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Summary of a supported provider for integrators
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderInfo {
+    pub name: String,
+    pub endpoint_template: String,
+    pub fields: Vec<String>,
+}
+
+/// Headers `build_request` always sets; extra headers may not repeat them
+const RESERVED_HEADERS: &[&str] = &[
+    "host",
+    "accept",
+    "accept-language",
+    "accept-encoding",
+    "connection",
+    "user-agent",
+];
+
+/// Most extra headers a request may carry
+pub const MAX_EXTRA_HEADERS: usize = 32;
+
+/// Parses a `Name: value` header line, as passed to `--header` or over FFI
+pub fn parse_header_line(line: &str) -> Result<(String, String), String> {
+    let (name, value) = line
+        .split_once(':')
+        .ok_or_else(|| format!("header {:?} is not in `Name: value` form", line))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
 
 #[derive(Debug, Clone)]
 pub struct ProviderConfig {
     pub provider_type: Provider,
+    pub auth_scheme: AuthScheme,
     pub cookie: String,
     pub access_token: String,
+    /// Caller-supplied headers sent after the auth headers, e.g. `X-Device-Id`
+    pub extra_headers: Vec<(String, String)>,
 }
 
 impl ProviderConfig {
     pub fn new(provider_type: Provider, cookie: String, access_token: String) -> Self {
         debug!("Configuring {} payment verification", provider_type);
 
-        ProviderConfig {
+        let mut config = ProviderConfig {
+            auth_scheme: provider_type.auth_scheme(),
             provider_type,
             cookie,
             access_token,
+            extra_headers: Vec::new(),
+        };
+        config.normalize_auth();
+        config
+    }
+
+    /// Applies the provider's token cookie rule: a missing access token is read from
+    /// the cookie, and a cookie lacking the token gets it added
+    fn normalize_auth(&mut self) {
+        let Some(name) = self.provider_type.token_cookie() else {
+            return;
+        };
+
+        match cookie_value(&self.cookie, name) {
+            Some(token) if self.access_token.trim().is_empty() => {
+                debug!("Using the access token from the {} cookie", name);
+                self.access_token = token.to_string();
+            }
+            Some(_) => {}
+            None if !self.access_token.trim().is_empty() => {
+                debug!("Adding the access token to the {} cookie", name);
+                let separator = match self.cookie.trim_end() {
+                    "" => "",
+                    cookie if cookie.ends_with(';') => " ",
+                    _ => "; ",
+                };
+                self.cookie = format!(
+                    "{}{}{}={}",
+                    self.cookie.trim_end(),
+                    separator,
+                    name,
+                    self.access_token
+                );
+            }
+            None => {}
+        }
+    }
+
+    pub fn with_auth_scheme(mut self, auth_scheme: AuthScheme) -> Self {
+        self.auth_scheme = auth_scheme;
+        self
+    }
+
+    pub fn with_extra_headers(mut self, extra_headers: &[(String, String)]) -> Self {
+        self.extra_headers = extra_headers.to_vec();
+        self
+    }
+
+    /// Rejects empty or whitespace-only credentials the auth scheme depends on, which
+    /// would otherwise send an unauthenticated request that fails confusingly later.
+    pub fn validate_credentials(&self) -> Result<(), ZkpError> {
+        let (needs_cookie, needs_token) = self.auth_scheme.required_credentials();
+        if needs_cookie && self.cookie.trim().is_empty() {
+            return Err(ZkpError::Config(format!(
+                "{} requires a session cookie",
+                self.provider_type
+            )));
+        }
+        if needs_token && self.access_token.trim().is_empty() {
+            return Err(ZkpError::Config(format!(
+                "{} requires an access token",
+                self.provider_type
+            )));
         }
+        Ok(())
     }
 
-    pub fn auth_headers(&self) -> Vec<(&str, &str)> {
-        vec![
-            ("Cookie", self.cookie.as_str()),
-            ("X-Access-Token", self.access_token.as_str()),
-        ]
+    /// Rejects extra headers that are malformed or would duplicate a header the
+    /// request already carries
+    pub fn validate_extra_headers(&self) -> Result<(), ZkpError> {
+        let auth_names: Vec<String> = self
+            .auth_headers()
+            .into_iter()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect();
+        if self.extra_headers.len() > MAX_EXTRA_HEADERS {
+            return Err(ZkpError::Config(format!(
+                "{} extra headers given, at most {} are allowed",
+                self.extra_headers.len(),
+                MAX_EXTRA_HEADERS
+            )));
+        }
+        for (name, value) in &self.extra_headers {
+            hyper::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| ZkpError::Config(format!("Invalid header name {:?}", name)))?;
+            hyper::header::HeaderValue::from_str(value)
+                .map_err(|_| ZkpError::Config(format!("Invalid value for header {}", name)))?;
+
+            let lowercase = name.to_ascii_lowercase();
+            if RESERVED_HEADERS.contains(&lowercase.as_str()) || auth_names.contains(&lowercase) {
+                return Err(ZkpError::Config(format!(
+                    "Header {} is already set for {} requests",
+                    name, self.provider_type
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn auth_headers(&self) -> Vec<(String, String)> {
+        match &self.auth_scheme {
+            AuthScheme::CookieAndToken => vec![
+                ("Cookie".to_string(), self.cookie.clone()),
+                ("X-Access-Token".to_string(), self.access_token.clone()),
+            ],
+            AuthScheme::Cookie => vec![("Cookie".to_string(), self.cookie.clone())],
+            AuthScheme::Bearer => vec![(
+                "Authorization".to_string(),
+                format!("Bearer {}", self.access_token),
+            )],
+            AuthScheme::Custom(headers) => headers.clone(),
+            AuthScheme::Anonymous => Vec::new(),
+        }
+    }
+
+    /// Auth headers followed by the extra headers
+    pub fn request_headers(&self) -> Vec<(String, String)> {
+        let mut headers = self.auth_headers();
+        headers.extend(self.extra_headers.iter().cloned());
+        headers
+    }
+}
+
+/// Value of the named cookie in a `Cookie` header value
+fn cookie_value<'a>(cookie: &'a str, name: &str) -> Option<&'a str> {
+    cookie.split(';').find_map(|pair| {
+        let (key, value) = pair.trim().split_once('=')?;
+        (key == name && !value.is_empty()).then_some(value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_hosts() {
+        assert_eq!(Provider::Wise.endpoint_host(), "wise.com");
+        assert_eq!(Provider::PayPal.endpoint_host(), "www.paypal.com");
+        assert!(Provider::endpoint_hosts().contains(&"secure.chase.com".to_string()));
+        assert_eq!(
+            Provider::endpoint_hosts().len(),
+            Provider::value_variants().len()
+        );
+    }
+
+    #[test]
+    fn test_provider_resolved_from_server_name() {
+        assert_eq!(
+            Provider::resolve(None, "www.paypal.com", &[]).unwrap(),
+            Provider::PayPal
+        );
+        assert_eq!(
+            Provider::resolve(Some(&Provider::Wise), "wise.com.", &[]).unwrap(),
+            Provider::Wise
+        );
+        // A host outside the provider's domain is only trusted when allowed
+        assert!(matches!(
+            Provider::resolve(Some(&Provider::Wise), "wise.internal", &[]),
+            Err(ZkpError::UnknownProvider(_))
+        ));
+        assert_eq!(
+            Provider::resolve(
+                Some(&Provider::Wise),
+                "wise.internal",
+                &["WISE.internal".to_string()]
+            )
+            .unwrap(),
+            Provider::Wise
+        );
+        assert!(matches!(
+            Provider::resolve(Some(&Provider::Wise), "app.revolut.com", &[]),
+            Err(ZkpError::ProviderMismatch { .. })
+        ));
+        assert_eq!(
+            Provider::resolve(None, "cash.app", &[]).unwrap(),
+            Provider::CashApp
+        );
+        assert!(matches!(
+            Provider::resolve(None, "notwise.com", &[]),
+            Err(ZkpError::UnknownProvider(_))
+        ));
+    }
+
+    #[test]
+    fn test_transaction_endpoint_encodes_identifiers() {
+        let endpoint = Provider::Wise
+            .transaction_endpoint(Some("12 34"), "tx/5?a=b")
+            .unwrap();
+        assert_eq!(
+            endpoint,
+            "https://wise.com/gateway/v3/profiles/12%2034/transfers/tx%2F5%3Fa%3Db"
+        );
+
+        assert!(Provider::Wise.transaction_endpoint(None, "5").is_err());
+        assert!(Provider::PayPal.transaction_endpoint(None, "5").is_ok());
+    }
+
+    #[test]
+    fn test_request_step_resolves_from_transaction_url() {
+        let url = "https://wise.com/gateway/v3/profiles/42/transfers/777";
+        let details = RequestStep::transaction(Provider::Wise.endpoint_template());
+        assert_eq!(details.resolve(&Provider::Wise, url).unwrap(), url);
+
+        let list = RequestStep {
+            endpoint_template: "https://wise.com/gateway/v1/profiles/{profile_id}/activities",
+            commit_fields: false,
+            ..details
+        };
+        assert_eq!(
+            list.resolve(&Provider::Wise, url).unwrap(),
+            "https://wise.com/gateway/v1/profiles/42/activities"
+        );
+        assert!(
+            list.resolve(&Provider::Wise, "https://wise.com/other/777")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_credentials_follows_auth_scheme() {
+        let wise = ProviderConfig::new(Provider::Wise, "session=1".into(), "  ".into());
+        assert!(matches!(
+            wise.validate_credentials(),
+            Err(ZkpError::Config(message)) if message.contains("access token")
+        ));
+
+        let paypal = ProviderConfig::new(Provider::PayPal, "session=1".into(), String::new());
+        assert!(paypal.validate_credentials().is_ok());
+
+        let anonymous = ProviderConfig::new(Provider::Wise, String::new(), String::new())
+            .with_auth_scheme(AuthScheme::Anonymous);
+        assert!(anonymous.validate_credentials().is_ok());
+    }
+
+    #[test]
+    fn test_access_token_normalized_through_cookie() {
+        let from_cookie = ProviderConfig::new(
+            Provider::Wise,
+            "session=1; appToken=abc".into(),
+            String::new(),
+        );
+        assert_eq!(from_cookie.access_token, "abc");
+        assert!(from_cookie.validate_credentials().is_ok());
+
+        let into_cookie = ProviderConfig::new(Provider::Wise, "session=1;".into(), "abc".into());
+        assert_eq!(into_cookie.cookie, "session=1; appToken=abc");
+
+        let unchanged = ProviderConfig::new(Provider::PayPal, "session=1".into(), "abc".into());
+        assert_eq!(unchanged.cookie, "session=1");
+    }
+
+    #[test]
+    fn test_extra_headers_follow_auth_headers() {
+        let extra = vec![parse_header_line("X-Device-Id:  abc-123 ").unwrap()];
+        let wise = ProviderConfig::new(Provider::Wise, "session=1".into(), "token".into())
+            .with_extra_headers(&extra);
+        assert!(wise.validate_extra_headers().is_ok());
+        assert_eq!(
+            wise.request_headers().last(),
+            Some(&("X-Device-Id".to_string(), "abc-123".to_string()))
+        );
+
+        assert!(parse_header_line("X-Device-Id").is_err());
+        for duplicate in ["cookie: a=b", "User-Agent: curl", "Bad Name: x"] {
+            let headers = vec![parse_header_line(duplicate).unwrap()];
+            assert!(
+                wise.clone()
+                    .with_extra_headers(&headers)
+                    .validate_extra_headers()
+                    .is_err()
+            );
+        }
     }
 }
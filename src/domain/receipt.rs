@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use k256::ecdsa::signature::{Signer, Verifier};
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::VerificationReport;
+use crate::error::ZkpError;
+
+/// The verified result a receipt vouches for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReceiptClaims {
+    pub server_name: String,
+    pub session_time: DateTime<Utc>,
+    pub transaction_id: Option<String>,
+    /// Proven field names and values, in report order
+    pub fields: Vec<(String, String)>,
+    /// When the verifier checked the presentation
+    pub verified_at: DateTime<Utc>,
+}
+
+/// A verifier's signature over a verification result, so it can vouch for the result
+/// downstream without anyone re-running verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    pub claims: ReceiptClaims,
+    /// Hex SEC1 compressed secp256k1 key of the signing verifier
+    pub signer_key: String,
+    /// Hex ECDSA signature over the claims' JSON encoding, hashed with SHA-256
+    pub signature: String,
+}
+
+impl SignedReceipt {
+    /// Checks the signature against the embedded signer key. Callers still decide
+    /// whether they trust that key.
+    pub fn verify(&self) -> Result<(), ZkpError> {
+        let key_bytes = hex::decode(&self.signer_key)
+            .map_err(|e| ZkpError::InvalidReceipt(format!("signer key is not hex: {}", e)))?;
+        let signer_key = VerifyingKey::from_sec1_bytes(&key_bytes)
+            .map_err(|e| ZkpError::InvalidReceipt(format!("bad signer key: {}", e)))?;
+        let signature_bytes = hex::decode(&self.signature)
+            .map_err(|e| ZkpError::InvalidReceipt(format!("signature is not hex: {}", e)))?;
+        let signature = Signature::from_slice(&signature_bytes)
+            .map_err(|e| ZkpError::InvalidReceipt(format!("bad signature: {}", e)))?;
+
+        signer_key
+            .verify(&serde_json::to_vec(&self.claims)?, &signature)
+            .map_err(|_| ZkpError::InvalidReceipt("signature does not match the claims".into()))
+    }
+}
+
+/// Signs the fields and timestamps of a successful verification with the verifier's key
+pub fn sign_verification_report(
+    report: &VerificationReport,
+    signing_key: &SigningKey,
+) -> Result<SignedReceipt, ZkpError> {
+    let claims = ReceiptClaims {
+        server_name: report.server_name.clone(),
+        session_time: report.session_time,
+        transaction_id: report.transaction_id.clone(),
+        fields: report
+            .fields
+            .iter()
+            .filter(|field| field.proven)
+            .map(|field| (field.name.clone(), field.value.clone()))
+            .collect(),
+        verified_at: Utc::now(),
+    };
+    let signature: Signature = signing_key.sign(&serde_json::to_vec(&claims)?);
+
+    Ok(SignedReceipt {
+        claims,
+        signer_key: hex::encode(signing_key.verifying_key().to_sec1_bytes()),
+        signature: hex::encode(signature.to_bytes()),
+    })
+}
+
+/// Parses a hex-encoded 32-byte secp256k1 secret key
+pub fn parse_signing_key(hex_key: &str) -> Result<SigningKey, ZkpError> {
+    let bytes = hex::decode(hex_key.trim())
+        .map_err(|e| ZkpError::Config(format!("receipt signing key is not hex: {}", e)))?;
+    SigningKey::from_slice(&bytes)
+        .map_err(|e| ZkpError::Config(format!("invalid receipt signing key: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::VerifiedField;
+
+    #[test]
+    fn test_receipt_signature_covers_claims() {
+        let mut report = VerificationReport::new(
+            "wise.com".to_string(),
+            DateTime::UNIX_EPOCH,
+            vec![VerifiedField {
+                name: "targetAmount".to_string(),
+                range: (0, 20),
+                raw: "\"targetAmount\":100.5".to_string(),
+                value: "100.5".to_string(),
+                proven: true,
+            }],
+        );
+        report.transaction_id = Some("987654321".to_string());
+        let signing_key = parse_signing_key(&"11".repeat(32)).unwrap();
+
+        let receipt = sign_verification_report(&report, &signing_key).unwrap();
+        assert!(receipt.verify().is_ok());
+
+        let json = serde_json::to_string(&receipt).unwrap();
+        let mut tampered: SignedReceipt = serde_json::from_str(&json).unwrap();
+        tampered.claims.fields[0].1 = "1000.5".to_string();
+        assert!(matches!(
+            tampered.verify(),
+            Err(ZkpError::InvalidReceipt(_))
+        ));
+
+        assert!(parse_signing_key("00").is_err());
+    }
+}
@@ -1,13 +1,253 @@
 use serde::Deserialize;
 
+/// Cipher suites the MPC-TLS prover can negotiate
+pub const MPC_TLS_CIPHER_SUITES: &[&str] = &[
+    "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+];
+
+/// Sent when a server sets no `accept_language`; en-US formats amounts with `.` decimals
+/// and no grouping quirks, which the field patterns rely on
+pub const DEFAULT_ACCEPT_LANGUAGE: &str = "en-US,en;q=0.9";
+
+/// HTTP protocol spoken over the notarized connection
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum HttpVersion {
+    /// HTTP/1.0 request line, for servers that treat versions differently. Requests
+    /// still carry `Host` and `Connection` headers, so multi-request flows keep working.
+    #[serde(rename = "1.0")]
+    Http10,
+    #[default]
+    #[serde(rename = "1.1")]
+    Http11,
+    /// Sent with prior knowledge, as MPC-TLS negotiates no ALPN. Headers are
+    /// HPACK-compressed, so only body fields can be committed and the request line,
+    /// and with it the transaction id, stays unproven.
+    #[serde(rename = "2")]
+    Http2,
+}
+
+/// TLS protocol version a server may negotiate
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// TLS server name to present when it differs from `host`, e.g. behind a fronting CDN
+    #[serde(default)]
+    pub sni_name: Option<String>,
+    /// Provider-specific User-Agent, overriding the global one
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// `Accept` header for this server, overriding `*/*`
+    #[serde(default)]
+    pub accept: Option<String>,
+    /// `Accept-Language` header for this server, overriding `DEFAULT_ACCEPT_LANGUAGE`
+    #[serde(default)]
+    pub accept_language: Option<String>,
+    /// HTTP version used for the notarized request; plain requests use it too unless
+    /// it is HTTP/2, which they send as HTTP/1.1
+    #[serde(default)]
+    pub http_version: HttpVersion,
+    /// TLS versions the non-notarized pre-flight requests may use; empty allows the
+    /// library defaults. The MPC-TLS prover can't be configured and always speaks TLS
+    /// 1.2, so only `1.2` is accepted.
+    #[serde(default)]
+    pub tls_versions: Vec<TlsVersion>,
+    /// IANA names of the cipher suites the pre-flight requests may use; empty allows
+    /// the defaults. Like `tls_versions` this doesn't reach the prover, so only
+    /// `MPC_TLS_CIPHER_SUITES` are accepted.
+    #[serde(default)]
+    pub cipher_suites: Vec<String>,
+    /// Terminal transaction states, e.g. `OUTGOING_PAYMENT_SENT`. When set, prove
+    /// refuses to notarize and verify rejects any other state, and
+    /// `--wait-for-settlement` waits for one of them.
+    #[serde(default)]
+    pub settled_states: Vec<String>,
+    /// Seconds between the non-notarized status polls of `--wait-for-settlement`
+    #[serde(default = "default_settle_poll_interval")]
+    pub settle_poll_interval: u64,
+    /// Times a non-notarized pre-flight request answered with 429 is retried after
+    /// waiting out its `Retry-After`; 0 fails straight away
+    #[serde(default)]
+    pub rate_limit_retries: u32,
+    /// Request header `--nonce` is sent in, e.g. `X-Request-Id`
+    #[serde(default)]
+    pub nonce_header: Option<String>,
+    /// Regex locating the server's echo of the nonce anywhere in its response, headers
+    /// included, with the nonce as the first capture group, e.g.
+    /// `(?i)x-request-id: ([A-Za-z0-9-]+)`. The match is committed and revealed.
+    #[serde(default)]
+    pub nonce_echo_pattern: Option<String>,
+    /// Fields every presentation must reveal, e.g. amount, currency, status and id.
+    /// Present refuses to write one missing any; empty requires nothing.
+    #[serde(default)]
+    pub required_fields: Vec<String>,
+    /// Request headers whose values are masked in logs; empty masks
+    /// `DEFAULT_REDACTED_HEADERS`. Set from the top-level `redacted_headers`.
+    #[serde(skip)]
+    pub redacted_headers: Vec<String>,
+    /// DNS-over-HTTPS endpoint the host is resolved through; system DNS when unset.
+    /// Set from the top-level `doh_url`.
+    #[serde(skip)]
+    pub doh_url: Option<String>,
+    /// Widest gap of JSON punctuation bridged between field ranges; prove and present
+    /// must agree on it. Set from the top-level `range_merge_gap`.
+    #[serde(skip)]
+    pub range_merge_gap: usize,
+}
+
+fn default_settle_poll_interval() -> u64 {
+    15
+}
+
+impl ServerConfig {
+    pub fn new(host: &str, port: u16) -> Self {
+        ServerConfig {
+            host: host.to_string(),
+            port,
+            sni_name: None,
+            user_agent: None,
+            accept: None,
+            accept_language: None,
+            http_version: HttpVersion::default(),
+            tls_versions: Vec::new(),
+            cipher_suites: Vec::new(),
+            settled_states: Vec::new(),
+            settle_poll_interval: default_settle_poll_interval(),
+            rate_limit_retries: 0,
+            nonce_header: None,
+            nonce_echo_pattern: None,
+            required_fields: Vec::new(),
+            redacted_headers: Vec::new(),
+            doh_url: None,
+            range_merge_gap: 0,
+        }
+    }
+
+    /// Server name used for the TLS handshake, defaulting to the host
+    pub fn sni(&self) -> &str {
+        self.sni_name.as_deref().unwrap_or(&self.host)
+    }
+
+    pub fn accept(&self) -> &str {
+        self.accept.as_deref().unwrap_or("*/*")
+    }
+
+    pub fn accept_language(&self) -> &str {
+        self.accept_language
+            .as_deref()
+            .unwrap_or(DEFAULT_ACCEPT_LANGUAGE)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() {
+            return Err("host must not be empty".to_string());
+        }
+        if self.port == 0 {
+            return Err(format!(
+                "port for {} must be between 1 and 65535",
+                self.host
+            ));
+        }
+        let invalid_sni = self.sni_name.as_deref().is_some_and(|sni_name| {
+            sni_name.is_empty()
+                || sni_name
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '/' || c == ':')
+        });
+        if invalid_sni {
+            return Err(format!(
+                "invalid sni_name {:?} for {}",
+                self.sni_name, self.host
+            ));
+        }
+        if self
+            .user_agent
+            .as_deref()
+            .is_some_and(|user_agent| user_agent.trim().is_empty())
+        {
+            return Err(format!("user_agent for {} must not be empty", self.host));
+        }
+        for (name, value) in [
+            ("accept", &self.accept),
+            ("accept_language", &self.accept_language),
+        ] {
+            if value
+                .as_deref()
+                .is_some_and(|value| value.trim().is_empty())
+            {
+                return Err(format!("{} for {} must not be empty", name, self.host));
+            }
+        }
+        // Only pre-flight requests honor these; anything the notarized MPC-TLS
+        // connection can't negotiate would make pre-flight succeed where prove fails
+        if let Some(version) = self
+            .tls_versions
+            .iter()
+            .find(|version| **version != TlsVersion::Tls12)
+        {
+            return Err(format!(
+                "tls_versions for {} include {:?}, but MPC-TLS only supports TLS 1.2",
+                self.host, version
+            ));
+        }
+        if let Some(suite) = self
+            .cipher_suites
+            .iter()
+            .find(|suite| !MPC_TLS_CIPHER_SUITES.contains(&suite.as_str()))
+        {
+            return Err(format!(
+                "cipher_suites for {} include {}, which MPC-TLS doesn't support ({})",
+                self.host,
+                suite,
+                MPC_TLS_CIPHER_SUITES.join(", ")
+            ));
+        }
+        if let Some(pattern) = &self.nonce_echo_pattern {
+            let captures = regex::Regex::new(pattern)
+                .map_err(|e| format!("invalid nonce_echo_pattern for {}: {}", self.host, e))?
+                .captures_len();
+            if captures < 2 {
+                return Err(format!(
+                    "nonce_echo_pattern for {} must capture the nonce in a group",
+                    self.host
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct NotaryConfig {
     pub server: ServerConfig,
     pub tls_enabled: bool,
+    /// Path the notary API is served under, e.g. `/notary/v1` behind a reverse proxy
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+}
+
+/// `path_prefix` without surrounding slashes, or `None` when it names no path
+pub fn normalize_path_prefix(path_prefix: Option<&str>) -> Option<&str> {
+    path_prefix
+        .map(|prefix| prefix.trim_matches('/'))
+        .filter(|prefix| !prefix.is_empty())
+}
+
+/// Deployment the notary is resolved for
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NotaryEnvironment {
+    #[default]
+    Local,
+    Staging,
+    Production,
 }
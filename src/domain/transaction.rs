@@ -1,8 +1,181 @@
-#[derive(Debug)]
+use rust_decimal::Decimal;
+use std::fmt;
+
+use crate::domain::{Provider, ZelleBank};
+use crate::error::ZkpError;
+
+/// Provider-agnostic summary of a notarized transaction
+#[derive(Debug, Clone, PartialEq)]
 pub struct TransactionMetadata {
     pub id: String,
+    /// Unsigned major-unit amount, e.g. `25.00`
     pub amount: String,
     pub currency: String,
     pub status: String,
-    pub date: String,
+    /// Provider timestamp as the response formats it, when it carries one
+    pub date: Option<String>,
+}
+
+impl fmt::Display for TransactionMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "transaction {}: {} {} ({})",
+            self.id, self.amount, self.currency, self.status
+        )?;
+        if let Some(date) = &self.date {
+            write!(f, " at {}", date)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps one provider's payment fields onto `TransactionMetadata`.
+///
+/// `field` looks up a matched field's value by the name used in the provider's patterns.
+/// Returns `None` when a field other than the date is missing.
+pub trait MetadataExtractor {
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata>;
+}
+
+pub struct WiseMetadata;
+pub struct PayPalMetadata;
+pub struct RevolutMetadata;
+pub struct MercadoPagoMetadata;
+pub struct CashAppMetadata;
+pub struct ChaseZelleMetadata;
+
+impl MetadataExtractor for WiseMetadata {
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata> {
+        Some(TransactionMetadata {
+            id: field("paymentId")?,
+            amount: field("targetAmount")?,
+            currency: field("targetCurrency")?,
+            status: field("state")?,
+            date: field("timestamp"),
+        })
+    }
+}
+
+impl MetadataExtractor for PayPalMetadata {
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata> {
+        Some(TransactionMetadata {
+            id: field("transactionId")?,
+            amount: field("amount")?,
+            currency: field("currency")?,
+            status: field("status")?,
+            date: None,
+        })
+    }
+}
+
+impl MetadataExtractor for RevolutMetadata {
+    /// Revolut amounts are signed minor units; two decimal places covers the
+    /// currencies it settles transfers in
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata> {
+        let minor_units: i64 = field("amount")?.parse().ok()?;
+        Some(TransactionMetadata {
+            id: field("transactionId")?,
+            amount: Decimal::new(minor_units, 2).abs().to_string(),
+            currency: field("currency")?,
+            status: field("state")?,
+            date: field("completedDate"),
+        })
+    }
+}
+
+impl MetadataExtractor for MercadoPagoMetadata {
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata> {
+        Some(TransactionMetadata {
+            id: field("paymentId")?,
+            amount: field("transaction_amount")?,
+            currency: field("currency_id")?,
+            status: field("status")?,
+            date: field("date_approved"),
+        })
+    }
+}
+
+impl MetadataExtractor for CashAppMetadata {
+    /// Cash App amounts are minor units; it only settles USD, which has two decimals
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata> {
+        let minor_units: i64 = field("amount")?.parse().ok()?;
+        Some(TransactionMetadata {
+            id: field("paymentToken")?,
+            amount: Decimal::new(minor_units, 2).to_string(),
+            currency: field("currency")?,
+            status: field("status")?,
+            date: field("createdAt"),
+        })
+    }
+}
+
+impl MetadataExtractor for ChaseZelleMetadata {
+    /// Zelle only moves USD, so the currency is implied rather than read
+    fn extract(&self, field: &dyn Fn(&str) -> Option<String>) -> Option<TransactionMetadata> {
+        Some(TransactionMetadata {
+            id: field("paymentId")?,
+            amount: field("amount")?,
+            currency: "USD".to_string(),
+            status: field("status")?,
+            date: field("sendDate"),
+        })
+    }
+}
+
+/// Fails unless `state` is one of `settled_states`; an empty list accepts any state
+pub fn check_settled(state: Option<&str>, settled_states: &[String]) -> Result<(), ZkpError> {
+    match state {
+        _ if settled_states.is_empty() => Ok(()),
+        Some(state) if settled_states.iter().any(|settled| settled == state) => Ok(()),
+        state => Err(ZkpError::NotSettled {
+            state: state.map(str::to_string),
+            settled: settled_states.to_vec(),
+        }),
+    }
+}
+
+impl Provider {
+    /// Name of the field patterns' transaction state field
+    pub fn status_field(&self) -> &'static str {
+        match self {
+            Provider::Wise | Provider::Revolut => "state",
+            Provider::PayPal
+            | Provider::MercadoPago
+            | Provider::CashApp
+            | Provider::Zelle(ZelleBank::Chase) => "status",
+        }
+    }
+
+    pub fn metadata_extractor(&self) -> &'static dyn MetadataExtractor {
+        match self {
+            Provider::Wise => &WiseMetadata,
+            Provider::PayPal => &PayPalMetadata,
+            Provider::Revolut => &RevolutMetadata,
+            Provider::MercadoPago => &MercadoPagoMetadata,
+            Provider::CashApp => &CashAppMetadata,
+            Provider::Zelle(ZelleBank::Chase) => &ChaseZelleMetadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_settled() {
+        let settled = vec!["OUTGOING_PAYMENT_SENT".to_string(), "COMPLETED".to_string()];
+
+        assert!(check_settled(Some("COMPLETED"), &settled).is_ok());
+        assert!(check_settled(Some("PROCESSING"), &[]).is_ok());
+        assert!(matches!(
+            check_settled(Some("PROCESSING"), &settled),
+            Err(ZkpError::NotSettled { state: Some(state), .. }) if state == "PROCESSING"
+        ));
+        assert!(matches!(
+            check_settled(None, &settled),
+            Err(ZkpError::NotSettled { state: None, .. })
+        ));
+    }
 }
@@ -0,0 +1,391 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{
+    domain::{CertificateInfo, Provider, is_known_currency},
+    error::ZkpError,
+    utils::patterns::NONCE_FIELD,
+};
+
+/// A payment field revealed in a verified presentation
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedField {
+    pub name: String,
+    /// Committed byte range of the full match
+    pub range: (usize, usize),
+    /// Revealed bytes exactly as committed, e.g. `"targetAmount":100.5`
+    pub raw: String,
+    /// Value captured by the field pattern, e.g. `100.5`
+    pub value: String,
+    /// False when part of the match, such as its key, was hidden, so the value isn't
+    /// proven to belong to this field. Unproven fields are listed but never checked.
+    pub proven: bool,
+}
+
+/// An expected field value that the presentation did not match
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMismatch {
+    pub name: String,
+    pub expected: String,
+    /// Revealed value, or `None` when the field was not revealed
+    pub actual: Option<String>,
+}
+
+/// Policy checks applied on top of cryptographic verification
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Reject presentations whose TLS session is older than this
+    pub max_age: Option<Duration>,
+    /// Skip wall-clock checks such as `max_age` so stored presentations verify the same
+    /// on any machine and date
+    pub offline: bool,
+    /// Hex notary verifying keys to accept; empty accepts any notary
+    pub trusted_notary_keys: Vec<String>,
+    /// Server names to accept; empty accepts any server
+    pub allowed_server_names: Vec<String>,
+    /// Transaction id the revealed request line must name. A presentation that doesn't
+    /// reveal the request line is rejected too, since its transaction is unproven.
+    pub expected_transaction_id: Option<String>,
+    /// Per-provider states the revealed transaction state must be one of; providers
+    /// without an entry accept any state
+    pub settled_states: HashMap<Provider, Vec<String>>,
+    /// Nonce the server must be shown to have echoed, binding the presentation to a
+    /// verifier-issued challenge
+    pub expected_nonce: Option<String>,
+    /// Per-provider `nonce_echo_pattern` locating the server's echo of the nonce
+    pub nonce_echo_patterns: HashMap<Provider, String>,
+}
+
+/// Outcome of verifying a presentation
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationReport {
+    pub server_name: String,
+    pub session_time: DateTime<Utc>,
+    /// Transaction id from the revealed request line, if it was revealed
+    pub transaction_id: Option<String>,
+    pub fields: Vec<VerifiedField>,
+    /// Revealed bytes of each field's range by field name, unlike `raw` not lossily
+    /// decoded, for callers doing their own parsing. Only fields whose whole range is
+    /// authenticated are included, so no `unauthed` filler appears here.
+    pub raw_bytes: HashMap<String, Vec<u8>>,
+    /// `presentation_fingerprint` of the verified presentation, for spotting duplicates
+    pub fingerprint: Option<String>,
+    /// Server certificate recorded in the bundle; prover-reported, not notarized
+    pub certificate: Option<CertificateInfo>,
+    /// Sent byte ranges that are part of the notarized transcript but were withheld
+    pub redacted_sent: Vec<(usize, usize)>,
+    /// Received byte ranges that are part of the notarized transcript but were withheld
+    pub redacted_received: Vec<(usize, usize)>,
+    /// Revealed amount normalized to a decimal
+    pub amount: Option<Decimal>,
+    /// Revealed currency, validated against ISO 4217
+    pub currency: Option<String>,
+    /// Revealed transfer fee, in the source currency
+    pub fee: Option<Decimal>,
+    /// Revealed exchange rate from the source to the target currency
+    pub exchange_rate: Option<Decimal>,
+    pub warnings: Vec<String>,
+}
+
+impl VerificationReport {
+    pub fn new(
+        server_name: String,
+        session_time: DateTime<Utc>,
+        fields: Vec<VerifiedField>,
+    ) -> Self {
+        VerificationReport {
+            server_name,
+            session_time,
+            transaction_id: None,
+            fields,
+            raw_bytes: HashMap::new(),
+            fingerprint: None,
+            certificate: None,
+            redacted_sent: Vec::new(),
+            redacted_received: Vec::new(),
+            amount: None,
+            currency: None,
+            fee: None,
+            exchange_rate: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// First proven field of a name; every check and normalization reads fields here
+    pub fn field(&self, name: &str) -> Option<&VerifiedField> {
+        self.fields
+            .iter()
+            .find(|field| field.proven && field.name == name)
+    }
+
+    /// Fills `raw_bytes` from the received transcript the field ranges index into.
+    /// `revealed` are its authenticated ranges; a field not within one of them would
+    /// carry filler bytes and is skipped. Like `field`, the first field of a name wins.
+    pub fn set_raw_bytes(&mut self, received: &[u8], revealed: &[(usize, usize)]) {
+        for field in &self.fields {
+            let (start, end) = field.range;
+            let authenticated = revealed
+                .iter()
+                .any(|&(range_start, range_end)| range_start <= start && end <= range_end);
+            if !authenticated {
+                continue;
+            }
+            if let Some(bytes) = received.get(start..end) {
+                self.raw_bytes
+                    .entry(field.name.clone())
+                    .or_insert_with(|| bytes.to_vec());
+            }
+        }
+    }
+
+    /// Parses the revealed amount and currency fields, flagging values that don't normalize
+    pub fn normalize_payment(&mut self, amount_field: &str, currency_field: &str) {
+        match self.field(amount_field).map(|field| field.value.clone()) {
+            Some(value) => match Decimal::from_str(&value) {
+                Ok(amount) => self.amount = Some(amount),
+                Err(e) => self
+                    .warnings
+                    .push(format!("Invalid {} value {:?}: {}", amount_field, value, e)),
+            },
+            None => self
+                .warnings
+                .push(format!("Amount field {} was not revealed", amount_field)),
+        }
+
+        match self
+            .field(currency_field)
+            .map(|field| field.value.to_uppercase())
+        {
+            Some(code) if is_known_currency(&code) => self.currency = Some(code),
+            Some(code) => self
+                .warnings
+                .push(format!("Unknown ISO 4217 currency code {:?}", code)),
+            None => self.warnings.push(format!(
+                "Currency field {} was not revealed",
+                currency_field
+            )),
+        }
+    }
+
+    /// Parses the fee and exchange rate when revealed. Both are optional, so only
+    /// values that don't parse are flagged.
+    pub fn normalize_conversion(&mut self, fee_field: &str, rate_field: &str) {
+        self.fee = self.optional_decimal(fee_field);
+        self.exchange_rate = self.optional_decimal(rate_field);
+    }
+
+    fn optional_decimal(&mut self, name: &str) -> Option<Decimal> {
+        let value = self.field(name)?.value.clone();
+        match Decimal::from_str(&value) {
+            Ok(decimal) => Some(decimal),
+            Err(e) => {
+                self.warnings
+                    .push(format!("Invalid {} value {:?}: {}", name, value, e));
+                None
+            }
+        }
+    }
+
+    /// Checks that the revealed request was for `expected`, so response fields can't be
+    /// passed off as belonging to another transaction
+    pub fn check_transaction_id(&self, expected: &str) -> Result<(), ZkpError> {
+        match self.transaction_id.as_deref() {
+            Some(actual) if actual == expected => Ok(()),
+            actual => Err(ZkpError::TransactionIdMismatch {
+                expected: expected.to_string(),
+                actual: actual.map(str::to_string),
+            }),
+        }
+    }
+
+    /// Checks that the revealed nonce echo is `expected`, so a proof made before the
+    /// challenge was issued can't be replayed
+    pub fn check_nonce(&self, expected: &str) -> Result<(), ZkpError> {
+        match self.field(NONCE_FIELD) {
+            Some(field) if field.value == expected => Ok(()),
+            actual => Err(ZkpError::NonceMismatch {
+                expected: expected.to_string(),
+                actual: actual.map(|field| field.value.clone()),
+            }),
+        }
+    }
+
+    /// Checks revealed values against the expected ones, e.g. from an off-chain order.
+    ///
+    /// Values that both parse as decimals compare numerically so `100.5` matches `100.50`.
+    /// Every mismatch is returned, not just the first.
+    pub fn assert_fields(
+        &self,
+        expected: &HashMap<String, String>,
+    ) -> Result<(), Vec<FieldMismatch>> {
+        let mut mismatches: Vec<FieldMismatch> = expected
+            .iter()
+            .filter_map(|(name, expected)| {
+                let actual = self.field(name).map(|field| field.value.clone());
+                let matches = actual
+                    .as_deref()
+                    .is_some_and(|actual| values_match(expected, actual));
+                (!matches).then(|| FieldMismatch {
+                    name: name.clone(),
+                    expected: expected.clone(),
+                    actual,
+                })
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        mismatches.sort_by(|a, b| a.name.cmp(&b.name));
+        Err(mismatches)
+    }
+}
+
+fn values_match(expected: &str, actual: &str) -> bool {
+    match (Decimal::from_str(expected), Decimal::from_str(actual)) {
+        (Ok(expected), Ok(actual)) => expected == actual,
+        _ => expected == actual,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> VerificationReport {
+        let field = |name: &str, raw: &str, value: &str| VerifiedField {
+            name: name.to_string(),
+            range: (0, raw.len()),
+            raw: raw.to_string(),
+            value: value.to_string(),
+            proven: true,
+        };
+        VerificationReport::new(
+            "wise.com".to_string(),
+            DateTime::UNIX_EPOCH,
+            vec![
+                field("targetAmount", "\"targetAmount\":100.5", "100.5"),
+                field("targetCurrency", "\"targetCurrency\":\"EUR\"", "EUR"),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_conversion_fields_are_optional() {
+        let mut report = report();
+        report.normalize_conversion("fee", "rate");
+        assert_eq!(report.fee, None);
+        assert_eq!(report.exchange_rate, None);
+        assert!(report.warnings.is_empty());
+
+        report.fields.push(VerifiedField {
+            name: "rate".to_string(),
+            range: (0, 0),
+            raw: "\"rate\":0.92".to_string(),
+            value: "0.92".to_string(),
+            proven: true,
+        });
+        report.normalize_conversion("fee", "rate");
+        assert_eq!(report.exchange_rate, Decimal::from_str("0.92").ok());
+    }
+
+    #[test]
+    fn test_raw_bytes_follow_field_ranges() {
+        let mut report = report();
+        report.fields[1].range = (21, 43);
+        let received = b"\"targetAmount\":100.5,\"targetCurrency\":\"EUR\"";
+        report.set_raw_bytes(received, &[(0, 20), (21, 43)]);
+
+        assert_eq!(report.raw_bytes["targetAmount"], b"\"targetAmount\":100.5");
+        assert_eq!(
+            report.raw_bytes["targetCurrency"],
+            b"\"targetCurrency\":\"EUR\""
+        );
+
+        // Part of the currency's range is filler, so its bytes are left out
+        let mut partial = self::report();
+        partial.fields[1].range = (21, 43);
+        partial.set_raw_bytes(received, &[(0, 20), (21, 30)]);
+        assert!(partial.raw_bytes.contains_key("targetAmount"));
+        assert!(!partial.raw_bytes.contains_key("targetCurrency"));
+    }
+
+    #[test]
+    fn test_check_transaction_id() {
+        let mut report = report();
+        assert!(matches!(
+            report.check_transaction_id("42"),
+            Err(ZkpError::TransactionIdMismatch { actual: None, .. })
+        ));
+
+        report.transaction_id = Some("42".to_string());
+        assert!(report.check_transaction_id("42").is_ok());
+        assert!(matches!(
+            report.check_transaction_id("43"),
+            Err(ZkpError::TransactionIdMismatch { actual: Some(actual), .. }) if actual == "42"
+        ));
+    }
+
+    #[test]
+    fn test_check_nonce() {
+        let mut report = report();
+        assert!(matches!(
+            report.check_nonce("c0ffee"),
+            Err(ZkpError::NonceMismatch { actual: None, .. })
+        ));
+
+        report.fields.push(VerifiedField {
+            name: NONCE_FIELD.to_string(),
+            range: (0, 20),
+            raw: "x-request-id: c0ffee".to_string(),
+            value: "c0ffee".to_string(),
+            proven: true,
+        });
+        assert!(report.check_nonce("c0ffee").is_ok());
+        assert!(matches!(
+            report.check_nonce("decaf"),
+            Err(ZkpError::NonceMismatch { actual: Some(actual), .. }) if actual == "c0ffee"
+        ));
+    }
+
+    #[test]
+    fn test_assert_fields_reports_every_mismatch() {
+        let report = report();
+
+        let expected = HashMap::from([
+            ("targetAmount".to_string(), "100.50".to_string()),
+            ("targetCurrency".to_string(), "EUR".to_string()),
+        ]);
+        assert!(report.assert_fields(&expected).is_ok());
+
+        let expected = HashMap::from([
+            ("targetAmount".to_string(), "99".to_string()),
+            ("targetCurrency".to_string(), "USD".to_string()),
+            ("id".to_string(), "42".to_string()),
+        ]);
+        let mismatches = report.assert_fields(&expected).unwrap_err();
+        assert_eq!(mismatches.len(), 3);
+        assert_eq!(mismatches[0].name, "id");
+        assert_eq!(mismatches[0].actual, None);
+        assert_eq!(mismatches[1].actual.as_deref(), Some("100.5"));
+        assert_eq!(mismatches[2].actual.as_deref(), Some("EUR"));
+    }
+
+    #[test]
+    fn test_unproven_fields_are_not_checked() {
+        let mut report = report();
+        report.fields[0].proven = false;
+
+        report.normalize_payment("targetAmount", "targetCurrency");
+        assert_eq!(report.amount, None);
+        assert_eq!(report.currency.as_deref(), Some("EUR"));
+
+        let expected = HashMap::from([("targetAmount".to_string(), "100.5".to_string())]);
+        let mismatches = report.assert_fields(&expected).unwrap_err();
+        assert_eq!(mismatches[0].actual, None);
+    }
+}
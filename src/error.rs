@@ -0,0 +1,146 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ZkpError {
+    #[error(
+        "Field patterns `{first}` {first_range:?} and `{second}` {second_range:?} matched overlapping byte ranges"
+    )]
+    OverlappingFields {
+        first: String,
+        first_range: (usize, usize),
+        second: String,
+        second_range: (usize, usize),
+    },
+
+    #[error("Field `{0}` was not found in the transcript")]
+    FieldNotFound(String),
+
+    #[error(
+        "Server returned a `{0}` encoded response; compressed responses are unsupported since payment fields can't be located and committed in compressed bytes"
+    )]
+    CompressedResponse(String),
+
+    #[error(
+        "Response body exceeded {limit} bytes; raise max_recv_data if this response is expected"
+    )]
+    ResponseTooLarge { limit: usize },
+
+    #[error(
+        "Response was truncated at max_recv_data ({limit} bytes), so payment fields may be missing; raise max_recv_data"
+    )]
+    Truncated { limit: usize },
+
+    #[error(
+        "Requests take {size} bytes, over max_sent_data ({limit} bytes); shorten the cookie or extra headers, or raise max_sent_data"
+    )]
+    RequestTooLarge { size: usize, limit: usize },
+
+    #[error("{direction} range {start}..{end} ({source_fields}) exceeds the {len}-byte transcript")]
+    RangeOutOfBounds {
+        direction: &'static str,
+        start: usize,
+        end: usize,
+        len: usize,
+        source_fields: String,
+    },
+
+    #[error(
+        "Rate limited by the provider{}",
+        .retry_after.map(|secs| format!("; retry after {secs}s")).unwrap_or_default()
+    )]
+    RateLimited { retry_after: Option<u64> },
+
+    #[error(
+        "Response declares Content-Length {declared}, but its body spans {actual} bytes of the transcript"
+    )]
+    LengthMismatch { declared: usize, actual: usize },
+
+    #[error("Failed to build presentation: {0}")]
+    Presentation(String),
+
+    #[error(
+        "Presentation is stale: session was {age_secs}s ago, maximum allowed age is {max_age_secs}s"
+    )]
+    StalePresentation { age_secs: u64, max_age_secs: u64 },
+
+    #[error(
+        "Artifact format version {found} does not match expected version {expected}; regenerate it with this build"
+    )]
+    VersionMismatch { found: u16, expected: u16 },
+
+    #[error(
+        "Presentation is for transaction {}, expected {expected}",
+        .actual.as_deref().unwrap_or("<unrevealed>")
+    )]
+    TransactionIdMismatch {
+        expected: String,
+        actual: Option<String>,
+    },
+
+    #[error(
+        "Server echoed nonce {}, expected {expected}",
+        .actual.as_deref().unwrap_or("<none>")
+    )]
+    NonceMismatch {
+        expected: String,
+        actual: Option<String>,
+    },
+
+    #[error("Transaction {transaction_id} was not found under any of the profiles {candidates:?}")]
+    NoMatchingProfile {
+        transaction_id: String,
+        candidates: Vec<String>,
+    },
+
+    #[error(
+        "Transaction state {} is not one of the settled states {settled:?}",
+        .state.as_deref().unwrap_or("<unrevealed>")
+    )]
+    NotSettled {
+        state: Option<String>,
+        settled: Vec<String>,
+    },
+
+    #[error("Presentation would not reveal the required fields {missing:?}")]
+    InsufficientDisclosure { missing: Vec<String> },
+
+    #[error("Presentation is for server {0}, which belongs to no known provider")]
+    UnknownProvider(String),
+
+    #[error("Presentation is for a {detected} server, not {given}")]
+    ProviderMismatch { given: String, detected: String },
+
+    #[error("Presentation is for server {0}, which is not an allowed server name")]
+    UnexpectedServer(String),
+
+    #[error("Invalid verification receipt: {0}")]
+    InvalidReceipt(String),
+
+    #[error("Notary key {0} is not in trusted_notary_keys")]
+    UntrustedNotary(String),
+
+    #[error("Notary at {host}:{port} is unusable: {reason}")]
+    NotaryConnection {
+        host: String,
+        port: u16,
+        reason: String,
+    },
+
+    #[error("Timed out during {stage} after {secs}s")]
+    Timeout { stage: &'static str, secs: u64 },
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("HTTP request failed: {0}")]
+    Http(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] bincode::Error),
+
+    #[error("JSON serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -1,11 +1,27 @@
+// C callers can't mark calls unsafe; every pointer is null-checked before use
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use once_cell::sync::OnceCell;
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use tokio::runtime::Runtime;
 
 static RUNTIME: OnceCell<Runtime> = OnceCell::new();
-static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+// Prove and verify share output files, so sessions run one at a time
+static SESSION_LOCK: Mutex<()> = Mutex::new(());
+static TOKEN_REFRESH: Mutex<TlsnTokenRefreshCallback> = Mutex::new(None);
+static LAST_OUTPUT_PATHS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // Per calling thread, so concurrent hosts never read each other's errors
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Returns a fresh access token, or null if none could be obtained.
+/// The string stays owned by the host and is copied before the callback returns.
+pub type TlsnTokenRefreshCallback = Option<extern "C" fn() -> *const c_char>;
 
 const TLSN_SUCCESS: i32 = 0;
 const TLSN_ERROR_INIT: i32 = -1;
@@ -14,7 +30,16 @@ const TLSN_ERROR_RUNTIME: i32 = -3;
 const TLSN_ERROR_UNKNOWN: i32 = -99;
 
 fn set_last_error(error: &str) {
-    *LAST_ERROR.lock().unwrap() = Some(error.to_string());
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error.to_string()));
+}
+
+/// Locks `mutex`, reporting a lock poisoned by an earlier panic through
+/// `tlsn_get_last_error` rather than panicking across the FFI boundary
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, i32> {
+    mutex.lock().map_err(|_| {
+        set_last_error("Internal state is unusable after an earlier panic");
+        TLSN_ERROR_RUNTIME
+    })
 }
 
 unsafe fn c_str_to_rust_str(ptr: *const c_char) -> Result<&'static str, &'static str> {
@@ -34,15 +59,21 @@ unsafe fn c_str_to_rust_option(ptr: *const c_char) -> Option<&'static str> {
     if ptr.is_null() {
         None
     } else {
-        unsafe {
-            match CStr::from_ptr(ptr).to_str() {
-                Ok(s) => Some(s),
-                Err(_) => None,
-            }
-        }
+        unsafe { CStr::from_ptr(ptr).to_str().ok() }
     }
 }
 
+/// Non-blank trimmed lines of a nullable string; empty when null or not UTF-8
+fn c_str_lines(ptr: *const c_char) -> Vec<String> {
+    unsafe { c_str_to_rust_option(ptr) }
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn tlsn_init() -> i32 {
     match Runtime::new() {
@@ -62,16 +93,33 @@ pub extern "C" fn tlsn_init() -> i32 {
 
 #[unsafe(no_mangle)]
 pub extern "C" fn tlsn_cleanup() {
-    // Clear any stored error
-    *LAST_ERROR.lock().unwrap() = None;
+    // Clear any error stored for this thread
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+/// Registers a callback invoked once when the provider rejects the access token with
+/// a 401 during `tlsn_prove`. Pass null to clear it.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_set_token_refresh_callback(callback: TlsnTokenRefreshCallback) {
+    // The whole value is replaced, so a poisoned lock holds nothing half-written
+    *TOKEN_REFRESH.lock().unwrap_or_else(PoisonError::into_inner) = callback;
 }
 
+/// Runs a prove/present session.
+///
+/// Artifacts are saved under the transaction `url` names, so present and verify need
+/// the same `url` to find them. `extra_headers` are sent with every provider request,
+/// as newline-separated `Name: value` lines; null sends none.
+///
+/// Calls are serialized: a second thread calling `tlsn_prove` or `tlsn_verify` blocks
+/// until the running session finishes, since both read and write the same artifact files.
 #[unsafe(no_mangle)]
 pub extern "C" fn tlsn_prove(
     mode: i32,
     url: *const c_char,
     cookie: *const c_char,
     access_token: *const c_char,
+    extra_headers: *const c_char,
     user_agent: *const c_char,
     provider_host: *const c_char,
     provider_port: u16,
@@ -118,6 +166,18 @@ pub extern "C" fn tlsn_prove(
     let cookie = unsafe { c_str_to_rust_option(cookie) };
     let access_token = unsafe { c_str_to_rust_option(access_token) };
 
+    let extra_headers: Vec<(String, String)> = match c_str_lines(extra_headers)
+        .iter()
+        .map(|line| crate::domain::parse_header_line(line))
+        .collect()
+    {
+        Ok(extra_headers) => extra_headers,
+        Err(e) => {
+            set_last_error(&e);
+            return TLSN_ERROR_INVALID;
+        }
+    };
+
     let mode = match mode {
         0 => crate::domain::Mode::Prove,
         1 => crate::domain::Mode::Present,
@@ -128,20 +188,42 @@ pub extern "C" fn tlsn_prove(
         }
     };
 
-    match rt.block_on(crate::prove(
-        &mode,
+    let token_refresh = match lock(&TOKEN_REFRESH) {
+        Ok(callback) => (*callback).map(|callback| {
+            move || unsafe { c_str_to_rust_option(callback()) }.map(str::to_string)
+        }),
+        Err(code) => return code,
+    };
+
+    let _session = match lock(&SESSION_LOCK) {
+        Ok(session) => session,
+        Err(code) => return code,
+    };
+    // Drop paths left over from an earlier session so only this call's files are reported
+    crate::utils::file_io::take_written_artifacts();
+    let server_config = crate::domain::ServerConfig::new(provider_host, provider_port);
+    let notary = crate::domain::NotaryConfig {
+        server: crate::domain::ServerConfig::new(notary_host, notary_port),
+        tls_enabled: notary_tls_enabled,
+        path_prefix: None,
+    };
+    let options = crate::ProveOptions {
         url,
         cookie,
         access_token,
-        user_agent,
-        provider_host,
-        provider_port,
-        notary_host,
-        notary_port,
-        notary_tls_enabled,
-        max_sent_data,
-        max_recv_data,
-    )) {
+        extra_headers: &extra_headers,
+        format: crate::domain::ArtifactFormat::Bincode,
+        token_refresh: token_refresh
+            .as_ref()
+            .map(|refresh| refresh as &crate::utils::providers::TokenRefresh),
+        ..crate::ProveOptions::new(user_agent, max_sent_data, max_recv_data)
+    };
+    let result = rt.block_on(crate::prove(&mode, &server_config, &notary, &options));
+    match lock(&LAST_OUTPUT_PATHS) {
+        Ok(mut paths) => *paths = crate::utils::file_io::take_written_artifacts(),
+        Err(code) => return code,
+    }
+    match result {
         Ok(_) => TLSN_SUCCESS,
         Err(e) => {
             set_last_error(&e.to_string());
@@ -150,8 +232,19 @@ pub extern "C" fn tlsn_prove(
     }
 }
 
+/// Verifies the saved presentation for the transaction `url` names, or the unkeyed one
+/// when it names none. Serialized with `tlsn_prove`.
+///
+/// `trusted_notary_keys` and `allowed_server_names` are newline-separated lists. Null
+/// or empty keys accept any notary; null or empty server names accept only the hosts
+/// of the supported providers' endpoints.
 #[unsafe(no_mangle)]
-pub extern "C" fn tlsn_verify(url: *const c_char, unauthed_bytes: *const c_char) -> i32 {
+pub extern "C" fn tlsn_verify(
+    url: *const c_char,
+    unauthed_bytes: *const c_char,
+    trusted_notary_keys: *const c_char,
+    allowed_server_names: *const c_char,
+) -> i32 {
     let rt = match RUNTIME.get() {
         Some(rt) => rt,
         None => {
@@ -176,7 +269,25 @@ pub extern "C" fn tlsn_verify(url: *const c_char, unauthed_bytes: *const c_char)
         }
     };
 
-    match rt.block_on(crate::verify(url, unauthed_bytes)) {
+    let mut options = crate::domain::VerifyOptions {
+        trusted_notary_keys: c_str_lines(trusted_notary_keys),
+        allowed_server_names: c_str_lines(allowed_server_names),
+        ..crate::domain::VerifyOptions::default()
+    };
+    if options.allowed_server_names.is_empty() {
+        options.allowed_server_names = crate::domain::Provider::endpoint_hosts();
+    }
+
+    let _session = match lock(&SESSION_LOCK) {
+        Ok(session) => session,
+        Err(code) => return code,
+    };
+    match rt.block_on(crate::verify(
+        url,
+        unauthed_bytes,
+        crate::domain::ArtifactFormat::Bincode,
+        &options,
+    )) {
         Ok(_) => TLSN_SUCCESS,
         Err(e) => {
             set_last_error(&e.to_string());
@@ -185,15 +296,165 @@ pub extern "C" fn tlsn_verify(url: *const c_char, unauthed_bytes: *const c_char)
     }
 }
 
+/// Resolves the provider host and opens and drops a TCP connection to it, so the first
+/// `tlsn_prove` skips cold DNS and connection setup. Sends no request and notarizes
+/// nothing; safe to call while the user is still entering details.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_warmup(provider_host: *const c_char, provider_port: u16) -> i32 {
+    let rt = match RUNTIME.get() {
+        Some(rt) => rt,
+        None => {
+            set_last_error("Library not initialized. Call tlsn_init() first.");
+            return TLSN_ERROR_INIT;
+        }
+    };
+
+    let provider_host = match unsafe { c_str_to_rust_str(provider_host) } {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("Invalid provider_host string");
+            return TLSN_ERROR_INVALID;
+        }
+    };
+
+    match rt.block_on(crate::utils::net::warmup(
+        provider_host,
+        provider_port,
+        crate::utils::net::DEFAULT_CONNECT_TIMEOUT,
+    )) {
+        Ok(()) => TLSN_SUCCESS,
+        Err(e) => {
+            set_last_error(&format!("Warmup failed: {}", e));
+            TLSN_ERROR_RUNTIME
+        }
+    }
+}
+
+/// Returns the library version, e.g. `0.1.0`.
+/// The string is static: it stays valid for the process lifetime and must not be freed.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Returns a JSON array describing the supported providers.
+/// The returned string must be released with `tlsn_free_string`.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_supported_providers() -> *const c_char {
+    let json = match serde_json::to_string(&crate::utils::supported_providers()) {
+        Ok(json) => json,
+        Err(e) => {
+            set_last_error(&format!("Failed to serialize providers: {}", e));
+            return std::ptr::null();
+        }
+    };
+
+    match CString::new(json) {
+        Ok(c_string) => c_string.into_raw(),
+        Err(_) => std::ptr::null(),
+    }
+}
+
+/// Returns a JSON array of the files written by the most recent `tlsn_prove` call, in
+/// write order. Files are named by provider and transaction; prove-to-present for Wise
+/// transaction 123456789 lists `wise.123456789.attestation.tlsn`, then the `secrets`,
+/// `certificate`, `presentation` and `bundle` files named the same way. A failed call
+/// lists whatever it wrote before failing.
+/// The returned string must be released with `tlsn_free_string`.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_last_output_paths() -> *const c_char {
+    let paths = match lock(&LAST_OUTPUT_PATHS) {
+        Ok(paths) => paths,
+        Err(_) => return std::ptr::null(),
+    };
+    let json = match serde_json::to_string(&*paths) {
+        Ok(json) => json,
+        Err(e) => {
+            set_last_error(&format!("Failed to serialize output paths: {}", e));
+            return std::ptr::null();
+        }
+    };
+
+    match CString::new(json) {
+        Ok(c_string) => c_string.into_raw(),
+        Err(_) => std::ptr::null(),
+    }
+}
+
+/// Returns the most recent error message set on the calling thread, or null if none
+/// is set. Release with `tlsn_free_error_string`.
 #[unsafe(no_mangle)]
 pub extern "C" fn tlsn_get_last_error() -> *const c_char {
-    let error_guard = LAST_ERROR.lock().unwrap();
-    match error_guard.as_ref() {
+    LAST_ERROR.with(|last| match last.borrow().as_ref() {
         Some(error) => match CString::new(error.as_str()) {
             Ok(c_string) => c_string.into_raw(),
             Err(_) => std::ptr::null(),
         },
         None => std::ptr::null(),
+    })
+}
+
+/// Checks provider credentials without notarizing.
+/// On success, `valid` is set to whether the provider accepted them.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_check_credentials(
+    provider_host: *const c_char,
+    provider_port: u16,
+    cookie: *const c_char,
+    access_token: *const c_char,
+    user_agent: *const c_char,
+    valid: *mut bool,
+) -> i32 {
+    let rt = match RUNTIME.get() {
+        Some(rt) => rt,
+        None => {
+            set_last_error("Library not initialized. Call tlsn_init() first.");
+            return TLSN_ERROR_INIT;
+        }
+    };
+
+    if valid.is_null() {
+        set_last_error("Null valid pointer");
+        return TLSN_ERROR_INVALID;
+    }
+
+    let provider_host = match unsafe { c_str_to_rust_str(provider_host) } {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("Invalid provider_host string");
+            return TLSN_ERROR_INVALID;
+        }
+    };
+
+    let user_agent = match unsafe { c_str_to_rust_str(user_agent) } {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("Invalid user_agent string");
+            return TLSN_ERROR_INVALID;
+        }
+    };
+
+    let cookie = unsafe { c_str_to_rust_option(cookie) };
+    let access_token = unsafe { c_str_to_rust_option(access_token) };
+
+    let provider = crate::domain::ProviderConfig::new(
+        crate::utils::parse_provider_from_url(provider_host),
+        cookie.unwrap_or("").to_string(),
+        access_token.unwrap_or("").to_string(),
+    );
+    let server = crate::domain::ServerConfig::new(provider_host, provider_port);
+
+    match rt.block_on(crate::utils::check_credentials(
+        &provider, &server, user_agent,
+    )) {
+        Ok(accepted) => {
+            unsafe { *valid = accepted };
+            TLSN_SUCCESS
+        }
+        Err(e) => {
+            set_last_error(&e.to_string());
+            TLSN_ERROR_UNKNOWN
+        }
     }
 }
 
@@ -205,3 +466,46 @@ pub extern "C" fn tlsn_free_error_string(ptr: *mut c_char) {
         }
     }
 }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn tlsn_free_string(ptr: *mut c_char) {
+    tlsn_free_error_string(ptr);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poisoned_lock_reported_as_error() {
+        let mutex = Mutex::new(());
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = mutex.lock().unwrap();
+            panic!("poison the lock");
+        });
+
+        assert_eq!(lock(&mutex).err(), Some(TLSN_ERROR_RUNTIME));
+        let error = tlsn_get_last_error() as *mut c_char;
+        assert!(!error.is_null());
+        tlsn_free_error_string(error);
+    }
+
+    #[test]
+    fn test_last_error_is_per_thread() {
+        set_last_error("main thread error");
+        std::thread::spawn(|| {
+            assert!(tlsn_get_last_error().is_null());
+            set_last_error("worker error");
+        })
+        .join()
+        .unwrap();
+
+        let error = tlsn_get_last_error() as *mut c_char;
+        let message = unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        tlsn_free_error_string(error);
+        assert_eq!(message, "main thread error");
+    }
+}
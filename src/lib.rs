@@ -5,213 +5,1075 @@ use tlsn_core::{
     CryptoProvider, Secrets, attestation::Attestation, presentation::Presentation,
     request::RequestConfig, transcript::TranscriptCommitConfig,
 };
+use tlsn_formats::http::{DefaultHttpCommitter, HttpCommit, HttpTranscript};
 use tlsn_prover::ProverConfig;
 use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 pub mod config;
 pub mod domain;
+pub mod error;
 pub mod ffi;
 pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-use domain::Mode;
-use domain::{ProviderConfig, ServerConfig};
+use domain::{ArtifactFormat, CommitStrategy, Mode};
+use domain::{
+    CertificateInfo, PresentationBundle, Provider, ProviderConfig, ServerConfig,
+    VerificationReport, VerifiedField, VerifyOptions,
+};
+use error::ZkpError;
 use utils::{file_io, notary, providers, text_parser};
 
 pub use ffi::*;
 
+/// Smallest `max_recv_data` accepted; below this even the response headers won't fit
+pub const MIN_RECV_DATA: usize = 1 << 10;
+
+/// Per-session settings for `prove` and `prove_with_notary`
+#[derive(Clone, Copy)]
+pub struct ProveOptions<'a> {
+    /// Transaction endpoint; may hold a `{profile_id}` placeholder to resolve first
+    pub url: Option<&'a str>,
+    pub cookie: Option<&'a str>,
+    pub access_token: Option<&'a str>,
+    /// Headers sent with every provider request, e.g. a nonce header
+    pub extra_headers: &'a [(String, String)],
+    pub user_agent: &'a str,
+    pub max_sent_data: usize,
+    pub max_recv_data: usize,
+    pub connect_timeout: std::time::Duration,
+    pub read_timeout: std::time::Duration,
+    pub commit_strategy: CommitStrategy,
+    pub format: ArtifactFormat,
+    /// Called once when the provider rejects the access token
+    pub token_refresh: Option<&'a providers::TokenRefresh>,
+    /// Shown the commit ranges before notarizing, and may cancel it
+    pub confirm_commit: Option<&'a utils::info::ConfirmCommit>,
+    /// Fields to present instead of the provider's default set, see `present`
+    pub reveal_fields: &'a [String],
+}
+
+impl<'a> ProveOptions<'a> {
+    /// Options with no URL or credentials and the default timeouts, commit strategy and
+    /// artifact format
+    pub fn new(user_agent: &'a str, max_sent_data: usize, max_recv_data: usize) -> Self {
+        ProveOptions {
+            url: None,
+            cookie: None,
+            access_token: None,
+            extra_headers: &[],
+            user_agent,
+            max_sent_data,
+            max_recv_data,
+            connect_timeout: utils::net::DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: utils::net::DEFAULT_READ_TIMEOUT,
+            commit_strategy: CommitStrategy::default(),
+            format: ArtifactFormat::default(),
+            token_refresh: None,
+            confirm_commit: None,
+            reveal_fields: &[],
+        }
+    }
+}
+
+/// Checks the notary is reachable, then runs `prove_with_notary` against it
 pub async fn prove(
     mode: &Mode,
-    url: Option<&str>,
-    cookie: Option<&str>,
-    access_token: Option<&str>,
+    server_config: &ServerConfig,
+    notary: &domain::NotaryConfig,
+    options: &ProveOptions<'_>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let notary_client = connect_notary(
+        mode,
+        &notary.server.host,
+        notary.server.port,
+        notary.tls_enabled,
+        notary.path_prefix.as_deref(),
+        options.user_agent,
+    )
+    .await?;
+
+    prove_with_notary(mode, &notary_client, server_config, options).await
+}
+
+/// Builds a client for the notary, first checking it is reachable when `mode` notarizes.
+///
+/// `path_prefix` is the path the notary API sits under, for notaries behind a reverse
+/// proxy; `None` targets the root.
+pub async fn connect_notary(
+    mode: &Mode,
+    host: &str,
+    port: u16,
+    tls_enabled: bool,
+    path_prefix: Option<&str>,
     user_agent: &str,
-    provider_host: &str,
-    provider_port: u16,
-    notary_host: &str,
-    notary_port: u16,
-    notary_tls_enabled: bool,
-    max_sent_data: usize,
-    max_recv_data: usize,
+) -> Result<NotaryClient, Box<dyn std::error::Error>> {
+    let path_prefix = domain::normalize_path_prefix(path_prefix);
+    if matches!(mode, Mode::Prove | Mode::ProveToPresent) {
+        notary::check_notary(host, port, tls_enabled, path_prefix, user_agent).await?;
+        info!("Requesting notarization from {}:{}", host, port);
+    }
+
+    let notary_client = NotaryClient::builder()
+        .host(host)
+        .port(port)
+        .path_prefix(path_prefix.unwrap_or_default())
+        .enable_tls(tls_enabled)
+        .build()
+        .map_err(|e| format!("Invalid notary client configuration: {}", e))?;
+    debug!("Notary client configured");
+
+    Ok(notary_client)
+}
+
+/// Like `prove`, with a pre-built notary client.
+///
+/// The client is only used to request sessions, so one client can serve many calls and
+/// tests can point it at an in-process notary. No notary health check is made.
+///
+/// Artifacts are saved as `{provider}.{transaction_id}.{kind}.tlsn` when `options.url` names a
+/// transaction, which is where `present`, `verify` and `verify_batch` look for them.
+pub async fn prove_with_notary(
+    mode: &Mode,
+    notary_client: &NotaryClient,
+    server_config: &ServerConfig,
+    options: &ProveOptions<'_>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let provider = utils::text_parser::parse_provider_from_url(provider_host);
+    let ProveOptions {
+        url,
+        cookie,
+        access_token,
+        extra_headers,
+        user_agent,
+        max_sent_data,
+        max_recv_data,
+        connect_timeout,
+        read_timeout,
+        commit_strategy,
+        format,
+        token_refresh,
+        confirm_commit,
+        reveal_fields,
+    } = *options;
+    let provider = utils::text_parser::parse_provider_from_url(&server_config.host);
+    // Artifacts are saved under the requested transaction so several can coexist
+    let transaction_id = url.and_then(text_parser::url_transaction_id);
+
+    if *mode == Mode::Verify {
+        return Err("Verify mode is handled by verify_artifact, not prove".into());
+    }
+
+    if *mode == Mode::Cleanup {
+        return Err("Cleanup mode is handled by file_io::shred_secrets, not prove".into());
+    }
+
+    if *mode == Mode::Present {
+        info!("Loading existing attestation for presentation");
+        present(
+            &provider,
+            server_config,
+            transaction_id.as_deref(),
+            reveal_fields,
+            format,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if max_recv_data < MIN_RECV_DATA {
+        return Err(ZkpError::Config(format!(
+            "max_recv_data is {} bytes, but at least {} are required",
+            max_recv_data, MIN_RECV_DATA
+        ))
+        .into());
+    }
+
+    if *mode == Mode::SelfTest {
+        info!("Running self-test against the fixture server");
+        utils::run_self_test(max_sent_data, max_recv_data).await?;
+        return Ok(());
+    }
 
     let provider_config = ProviderConfig::new(
         provider.clone(),
         cookie.unwrap_or("").to_string(),
         access_token.unwrap_or("").to_string(),
-    );
+    )
+    .with_extra_headers(extra_headers);
+    provider_config.validate_credentials()?;
+    provider_config.validate_extra_headers()?;
 
-    let server_config = ServerConfig {
-        host: provider_host.to_string(),
-        port: provider_port,
+    info!("Starting ZKP2P payment attestation for url {:?}", url);
+
+    // Resolve a `{profile_id}` placeholder before the notarized session starts
+    let url = match url {
+        Some(url) if url.contains("{profile_id}") => {
+            let profile_id =
+                providers::fetch_profile_id(&provider_config, server_config, user_agent).await?;
+            info!("Resolved profile id {}", profile_id);
+            Some(url.replace("{profile_id}", &profile_id))
+        }
+        url => url.map(str::to_string),
     };
 
-    info!("Starting ZKP2P payment attestation for url {:?}", url);
+    if let Some(url) = &url {
+        providers::check_request_size(
+            url,
+            &provider_config,
+            server_config,
+            user_agent,
+            max_sent_data,
+        )?;
+    }
 
-    let (attestation, secrets, (header_start, header_end), field_ranges) = if *mode != Mode::Present
-    {
-        info!(
-            "Requesting notarization from {}:{}",
-            notary_host, notary_port
-        );
+    // A failed inspection only costs the certificate details
+    let certificate = match utils::inspect_certificate(server_config).await {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            warn!(
+                "Could not inspect the certificate of {}: {:#}",
+                server_config.host, e
+            );
+            None
+        }
+    };
 
-        let notary_client = NotaryClient::builder()
-            .host(notary_host)
-            .port(notary_port)
-            .enable_tls(notary_tls_enabled)
-            .build()
-            .unwrap();
-        debug!("Notary client configured");
-
-        let accepted =
-            notary::request_notarization(&notary_client, max_sent_data, max_recv_data).await?;
-        debug!("Notarization request accepted");
-
-        let prover_config = ProverConfig::builder()
-            .server_name(server_config.host.as_str())
-            .protocol_config(
-                ProtocolConfig::builder()
-                    .max_sent_data(max_sent_data)
-                    .max_recv_data(max_recv_data)
-                    .build()?,
+    let (mut request_sender, prover_task) = match open_mpc_tls_session(
+        notary_client,
+        server_config,
+        max_sent_data,
+        max_recv_data,
+        connect_timeout,
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(e) if is_transient(e.as_ref()) => {
+            let first_error = e.to_string();
+            warn!(
+                "MPC-TLS setup failed ({}), retrying with a new notary session",
+                first_error
+            );
+            open_mpc_tls_session(
+                notary_client,
+                server_config,
+                max_sent_data,
+                max_recv_data,
+                connect_timeout,
             )
-            .crypto_provider(tlsn_core::CryptoProvider::default())
-            .build()
-            .ok()
-            .ok_or("Failed to build prover config")?;
-        debug!("Prover configuration built for {}", server_config.host);
-
-        let prover = tlsn_prover::Prover::new(prover_config)
-            .setup(accepted.io.compat())
-            .await?;
-        debug!("MPC-TLS prover initialized");
-
-        let client_socket =
-            tokio::net::TcpStream::connect((server_config.host.as_str(), server_config.port))
-                .await?;
-        debug!("Connected to {}:{}", server_config.host, server_config.port);
-
-        let (mpc_tls_connection, prover_fut) = prover.connect(client_socket.compat()).await?;
-        let mpc_tls_connection = TokioIo::new(mpc_tls_connection.compat());
-        let prover_task = tokio::spawn(prover_fut);
-        let (mut request_sender, connection) =
-            hyper::client::conn::http1::handshake(mpc_tls_connection).await?;
-        tokio::spawn(connection);
-        debug!("MPC-TLS connection established");
-
-        providers::execute_transaction_request(
+            .await
+            .map_err(|retry_error| {
+                format!(
+                    "MPC-TLS setup failed twice: {} (first attempt: {})",
+                    retry_error, first_error
+                )
+            })?
+        }
+        Err(e) => return Err(e),
+    };
+
+    let url = url.as_deref().ok_or("URL is required for prove mode")?;
+    let request_steps = provider.request_steps();
+    tokio::time::timeout(
+        read_timeout,
+        providers::execute_request_steps(
             &mut request_sender,
-            url.ok_or("URL is required for prove mode")?,
+            url,
             &provider_config,
-            &server_config,
+            server_config,
             user_agent,
-        )
-        .await?;
-        debug!("Transaction request executed");
+            max_recv_data,
+            token_refresh,
+        ),
+    )
+    .await
+    .map_err(|_| ZkpError::Timeout {
+        stage: "provider response",
+        secs: read_timeout.as_secs(),
+    })??;
+    // Dropping the sender lets hyper close a kept-alive connection
+    drop(request_sender);
+    debug!("Executed {} request steps", request_steps.len());
 
-        let mut prover = prover_task.await??;
-        let mut builder = TranscriptCommitConfig::builder(prover.transcript());
+    let mut prover = prover_task.await??;
+    if text_parser::is_truncated(prover.transcript().received(), max_recv_data) {
+        return Err(ZkpError::Truncated {
+            limit: max_recv_data,
+        }
+        .into());
+    }
+    let mut builder = TranscriptCommitConfig::builder(prover.transcript());
 
-        let header_range = text_parser::find_host_header_range(prover.transcript().sent()).unwrap();
-        builder.commit_sent(&(header_range.0..header_range.1))?;
-        debug!("Committed to host header range: {:?}", header_range);
+    let is_http2 = text_parser::is_http2(prover.transcript().sent());
+    // Sent and received ranges committed, kept for the commit preview
+    let (sent_ranges, recv_ranges) = match commit_strategy {
+        CommitStrategy::FullHttp if is_http2 => {
+            return Err("The full-http commit strategy requires HTTP/1.1".into());
+        }
+        CommitStrategy::Fields => {
+            let (sent_ranges, recv_ranges) = field_commit_ranges(
+                prover.transcript().sent(),
+                prover.transcript().received(),
+                &provider,
+                server_config,
+            )?;
+            for (start, end) in &sent_ranges {
+                builder.commit_sent(&(*start..*end))?;
+            }
+            for (start, end) in &recv_ranges {
+                builder.commit_recv(&(*start..*end))?;
+            }
+            debug!(
+                "Committed to {} request ranges and {} payment field ranges",
+                sent_ranges.len(),
+                recv_ranges.len()
+            );
+            (sent_ranges, recv_ranges)
+        }
+        CommitStrategy::FullHttp => {
+            // Covers the Host header and every JSON field, so present works unchanged
+            let transcript = HttpTranscript::parse(prover.transcript())?;
+            DefaultHttpCommitter::default().commit_transcript(&mut builder, &transcript)?;
+            debug!("Committed to the full HTTP transcript");
+            // Previewed whole; the committer splits it into many smaller ranges
+            (
+                vec![(0, prover.transcript().sent().len())],
+                vec![(0, prover.transcript().received().len())],
+            )
+        }
+    };
 
-        let field_ranges =
-            text_parser::find_field_ranges(prover.transcript().received(), &provider);
-        for (start, end) in &field_ranges {
-            builder.commit_recv(&(*start..*end))?;
+    match text_parser::analyze_transcript(prover.transcript().received(), &provider) {
+        Some(metadata) => info!("Notarizing {}", metadata),
+        None => warn!("Could not summarize the {} transaction", provider),
+    }
+    // A pending payment would only yield a proof the verifier rejects
+    let state = text_parser::find_fields(prover.transcript().received(), &provider)
+        .into_iter()
+        .find(|field| field.name == provider.status_field())
+        .map(|field| field.value);
+    domain::check_settled(state.as_deref(), &server_config.settled_states)?;
+    // A nonce sent in the configured header must come back, or the proof can't show it
+    let nonce = server_config.nonce_header.as_deref().and_then(|header| {
+        extra_headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(header))
+    });
+    if let Some((_, nonce)) = nonce {
+        let echoed =
+            nonce_echo(prover.transcript().received(), server_config).map(|field| field.value);
+        if echoed.as_deref() != Some(nonce.as_str()) {
+            return Err(ZkpError::NonceMismatch {
+                expected: nonce.clone(),
+                actual: echoed,
+            }
+            .into());
         }
-        debug!("Committed to {} payment field ranges", field_ranges.len());
+        info!("Server echoed nonce {}", nonce);
+    }
 
-        let transcript_commit = builder.build()?;
-        let mut builder = RequestConfig::builder();
-        builder.transcript_commit(transcript_commit);
-        debug!("Attestation request built");
+    if let Some(confirm_commit) = confirm_commit {
+        let mut preview =
+            utils::info::commit_preview("sent", prover.transcript().sent(), &sent_ranges);
+        preview.extend(utils::info::commit_preview(
+            "received",
+            prover.transcript().received(),
+            &recv_ranges,
+        ));
+        if !confirm_commit(&preview) {
+            return Err("Notarization cancelled at the commit preview".into());
+        }
+    }
 
-        let request_config = builder.build()?;
-        #[allow(deprecated)]
-        let (attestation, secrets) = prover.notarize(&request_config).await?;
-        info!("Notarization completed successfully");
+    let transcript_commit = builder.build()?;
+    let mut builder = RequestConfig::builder();
+    builder.transcript_commit(transcript_commit);
+    debug!("Attestation request built");
 
-        (attestation, secrets, header_range, field_ranges)
-    } else {
-        info!("Loading existing attestation for presentation");
-        let attestation_path = file_io::get_file_path(&provider.to_string(), "attestation");
-        let secrets_path = file_io::get_file_path(&provider.to_string(), "secrets");
+    let request_config = builder.build()?;
+    #[allow(deprecated)]
+    let (attestation, secrets) = prover.notarize(&request_config).await?;
+    info!("Notarization completed successfully");
 
-        let attestation: Attestation = bincode::deserialize(&std::fs::read(attestation_path)?)?;
-        let secrets: Secrets = bincode::deserialize(&std::fs::read(secrets_path)?)?;
-        debug!("Loaded attestation and secrets from disk");
+    // Saved in both modes so a failed presentation step can resume without re-notarizing
+    let transaction_id = transaction_id.as_deref();
+    file_io::save_file(
+        &provider,
+        transaction_id,
+        "attestation",
+        &attestation,
+        format,
+    )
+    .await?;
+    file_io::save_file(&provider, transaction_id, "secrets", &secrets, format).await?;
+    file_io::save_file(
+        &provider,
+        transaction_id,
+        "certificate",
+        &certificate,
+        format,
+    )
+    .await?;
+    info!("Attestation completed and saved");
+    if *mode == Mode::Prove {
+        return Ok(());
+    }
 
-        let header_range =
-            text_parser::find_host_header_range(secrets.transcript().sent()).unwrap();
-        let field_ranges =
-            text_parser::find_field_ranges(secrets.transcript().received(), &provider);
-        debug!(
-            "Parsed {} field ranges for selective disclosure",
-            field_ranges.len()
+    let presentation = build_presentation(
+        &attestation,
+        &secrets,
+        &provider,
+        server_config,
+        reveal_fields,
+    )?;
+    save_presentation(&provider, transaction_id, &presentation, format).await?;
+    // Kept only for resuming a failed presentation step, which is no longer needed
+    file_io::shred_secrets(&provider, transaction_id).await?;
+
+    Ok(())
+}
+
+/// Sent and received byte ranges of a transcript
+type TranscriptRanges = (Vec<(usize, usize)>, Vec<(usize, usize)>);
+
+/// Ranges committed under the `fields` strategy: the Host header and request line of
+/// an HTTP/1.1 request, then the payment fields of the responses and the nonce echo
+/// when `server_config` has a `nonce_echo_pattern`
+fn field_commit_ranges(
+    sent: &[u8],
+    received: &[u8],
+    provider: &Provider,
+    server_config: &ServerConfig,
+) -> Result<TranscriptRanges, Box<dyn std::error::Error>> {
+    let merge_gap = server_config.range_merge_gap;
+    if text_parser::is_http2(sent) {
+        warn!(
+            "HTTP/2 headers are compressed, so the request line can't be committed and the transaction id stays unproven"
         );
+        let fields = text_parser::find_http2_fields(received, provider);
+        let field_ranges =
+            text_parser::coalesce_ranges(received, &text_parser::field_ranges(&fields)?, merge_gap);
+        text_parser::check_range_bounds("Received", &field_ranges, received.len(), &fields)?;
+        return Ok((Vec::new(), field_ranges));
+    }
 
-        (attestation, secrets, header_range, field_ranges)
-    };
+    let header_range = text_parser::find_host_header_range(sent)
+        .ok_or("Host header not found in sent transcript")?;
+    debug!("Host header range: {:?}", header_range);
+    let request_line_range = text_parser::find_request_line_range(sent)
+        .ok_or("Request line not found in sent transcript")?;
+    debug!("Request line range: {:?}", request_line_range);
 
-    if *mode == Mode::Prove {
-        file_io::save_file(&provider, "attestation", &attestation).await?;
-        file_io::save_file(&provider, "secrets", &secrets).await?;
-        info!("Attestation completed and saved");
-        return Ok(());
+    let transaction_id = text_parser::request_transaction_id(sent);
+    let mut fields = text_parser::find_step_fields(
+        received,
+        provider,
+        transaction_id.as_deref(),
+        &provider.request_steps(),
+    );
+    fields.extend(nonce_echo(received, server_config));
+    let field_ranges =
+        text_parser::coalesce_ranges(received, &text_parser::field_ranges(&fields)?, merge_gap);
+    let sent_ranges = vec![header_range, request_line_range];
+    text_parser::check_range_bounds("Sent", &sent_ranges, sent.len(), &[])?;
+    text_parser::check_range_bounds("Received", &field_ranges, received.len(), &fields)?;
+    Ok((sent_ranges, field_ranges))
+}
+
+/// Runs the commit and reveal logic on a saved transcript instead of a notarized session.
+///
+/// `fixture` holds the raw HTTP/1.1 request followed by the raw response, as they went
+/// over the wire. Nothing is notarized or saved; the ranges that would be committed and
+/// revealed are logged, for developing field patterns without live credentials.
+pub fn prove_fixture(
+    provider: &Provider,
+    server_config: &ServerConfig,
+    fixture: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (sent, received) = text_parser::split_fixture(fixture)
+        .ok_or("Fixture must hold a request head followed by an HTTP/1.x response")?;
+    info!(
+        "Fixture holds {} sent and {} received bytes",
+        sent.len(),
+        received.len()
+    );
+
+    let (sent_ranges, recv_ranges) = field_commit_ranges(sent, received, provider, server_config)?;
+    let mut preview = utils::info::commit_preview("sent", sent, &sent_ranges);
+    preview.extend(utils::info::commit_preview(
+        "received",
+        received,
+        &recv_ranges,
+    ));
+    utils::info::print_commit_preview(&preview);
+
+    match text_parser::analyze_transcript(received, provider) {
+        Some(metadata) => info!("Would notarize {}", metadata),
+        None => warn!("Could not summarize the {} transaction", provider),
     }
 
+    let (sent_ranges, recv_ranges) =
+        presentation_reveal_ranges(sent, received, provider, server_config, &[])?;
+    utils::print_disclosure_coverage(
+        "Sent",
+        &utils::DisclosureCoverage::new(sent.len(), &sent_ranges),
+    );
+    utils::print_disclosure_coverage(
+        "Received",
+        &utils::DisclosureCoverage::new(received.len(), &recv_ranges),
+    );
+    info!(
+        "Verifiers would see: {}",
+        utils::info::render_redacted(received, &recv_ranges, '*')
+    );
+
+    Ok(())
+}
+
+type ProverTask = utils::AbortOnDrop<
+    Result<tlsn_prover::Prover<tlsn_prover::state::Closed>, tlsn_prover::ProverError>,
+>;
+
+/// Requests a notary session and opens the MPC-TLS connection to the server
+async fn open_mpc_tls_session(
+    notary_client: &NotaryClient,
+    server_config: &ServerConfig,
+    max_sent_data: usize,
+    max_recv_data: usize,
+    connect_timeout: std::time::Duration,
+) -> Result<(utils::RequestSender, ProverTask), Box<dyn std::error::Error>> {
+    let accepted =
+        notary::request_notarization(notary_client, max_sent_data, max_recv_data).await?;
+    debug!("Notarization request accepted");
+
+    let prover_config = ProverConfig::builder()
+        .server_name(server_config.sni())
+        .protocol_config(
+            ProtocolConfig::builder()
+                .max_sent_data(max_sent_data)
+                .max_recv_data(max_recv_data)
+                .build()?,
+        )
+        .crypto_provider(tlsn_core::CryptoProvider::default())
+        .build()
+        .ok()
+        .ok_or("Failed to build prover config")?;
+    debug!(
+        "Prover configuration built for {} (SNI {})",
+        server_config.host,
+        server_config.sni()
+    );
+
+    let prover = tlsn_prover::Prover::new(prover_config)
+        .setup(accepted.io.compat())
+        .await?;
+    debug!("MPC-TLS prover initialized");
+
+    let client_socket = tokio::time::timeout(
+        connect_timeout,
+        utils::connect_tcp(
+            &server_config.host,
+            server_config.port,
+            server_config.doh_url.as_deref(),
+        ),
+    )
+    .await
+    .map_err(|_| ZkpError::Timeout {
+        stage: "provider connect",
+        secs: connect_timeout.as_secs(),
+    })??;
+    debug!("Connected to {}:{}", server_config.host, server_config.port);
+
+    let (mpc_tls_connection, prover_fut) = prover.connect(client_socket.compat()).await?;
+    let mpc_tls_connection = TokioIo::new(mpc_tls_connection.compat());
+    let prover_task = utils::AbortOnDrop::spawn(prover_fut);
+    let request_sender =
+        utils::RequestSender::handshake(mpc_tls_connection, server_config.http_version).await?;
+    debug!(
+        "MPC-TLS connection established ({:?})",
+        server_config.http_version
+    );
+
+    Ok((request_sender, prover_task))
+}
+
+/// Whether a setup failure looks like a network hiccup worth one retry.
+///
+/// Only I/O-level resets, timeouts and unexpected EOFs qualify; certificate and
+/// authentication failures never do.
+fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    use std::io::ErrorKind;
+
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_error.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::UnexpectedEof
+            );
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// Builds a presentation from previously saved attestation and secrets.
+///
+/// Only the fields named in `reveal_fields` are disclosed; an empty list reveals every
+/// field matched for the provider except its opt-in ones, such as Wise's conversion
+/// details. This lets callers produce several presentations with different disclosures
+/// without re-notarizing. `server_config` supplies the `required_fields`, nonce echo
+/// and range merge gap of the provider.
+pub async fn present(
+    provider: &Provider,
+    server_config: &ServerConfig,
+    transaction_id: Option<&str>,
+    reveal_fields: &[String],
+    format: ArtifactFormat,
+) -> Result<(), ZkpError> {
+    let attestation: Attestation =
+        file_io::load_file(provider, transaction_id, "attestation", format)?;
+    let secrets: Secrets = file_io::load_file(provider, transaction_id, "secrets", format)?;
+    debug!("Loaded attestation and secrets from disk");
+
+    let presentation = build_presentation(
+        &attestation,
+        &secrets,
+        provider,
+        server_config,
+        reveal_fields,
+    )?;
+    save_presentation(provider, transaction_id, &presentation, format).await
+}
+
+/// Builds the presentation from the saved attestation when its request was for `url`,
+/// then removes the secrets.
+///
+/// Lets prove-to-present recover after a crash between saving the attestation and
+/// presenting, without another MPC-TLS round. Returns `false` when there is nothing to
+/// resume: no saved secrets, secrets for another request target, or artifacts written
+/// by an incompatible crate version.
+pub async fn resume_presentation(
+    provider: &Provider,
+    server_config: &ServerConfig,
+    url: &str,
+    reveal_fields: &[String],
+    format: ArtifactFormat,
+) -> Result<bool, ZkpError> {
+    let requested = text_parser::url_transaction_id(url);
+    let secrets: Secrets =
+        match file_io::load_file(provider, requested.as_deref(), "secrets", format) {
+            Ok(secrets) => secrets,
+            Err(ZkpError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e @ ZkpError::VersionMismatch { .. }) => {
+                warn!("Not resuming from the saved attestation: {}", e);
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        };
+
+    // The transaction id alone could match a request under another host or profile
+    if !text_parser::request_matches_url(secrets.transcript().sent(), url) {
+        debug!("Saved attestation is not for a request to {}", url);
+        return Ok(false);
+    }
+
+    let attestation: Attestation =
+        file_io::load_file(provider, requested.as_deref(), "attestation", format)?;
+    info!(
+        "Resuming from the saved attestation for {}; pass --force to re-notarize",
+        url
+    );
+    let presentation = build_presentation(
+        &attestation,
+        &secrets,
+        provider,
+        server_config,
+        reveal_fields,
+    )?;
+    save_presentation(provider, requested.as_deref(), &presentation, format).await?;
+    file_io::shred_secrets(provider, requested.as_deref()).await?;
+    Ok(true)
+}
+
+fn build_presentation(
+    attestation: &Attestation,
+    secrets: &Secrets,
+    provider: &Provider,
+    server_config: &ServerConfig,
+    reveal_fields: &[String],
+) -> Result<Presentation, ZkpError> {
     info!("Building selective disclosure presentation");
+    let sent = secrets.transcript().sent();
+    let (sent_ranges, field_ranges) = presentation_reveal_ranges(
+        sent,
+        secrets.transcript().received(),
+        provider,
+        server_config,
+        reveal_fields,
+    )?;
+
+    utils::print_disclosure_coverage(
+        "Sent",
+        &utils::DisclosureCoverage::new(sent.len(), &sent_ranges),
+    );
+    utils::print_disclosure_coverage(
+        "Received",
+        &utils::DisclosureCoverage::new(secrets.transcript().received().len(), &field_ranges),
+    );
+
     let mut builder = secrets.transcript_proof_builder();
-    builder.reveal_sent(&(header_start..header_end))?;
+    for (start, end) in &sent_ranges {
+        builder
+            .reveal_sent(&(*start..*end))
+            .map_err(|e| ZkpError::Presentation(e.to_string()))?;
+    }
     for (start, end) in &field_ranges {
-        builder.reveal_recv(&(*start..*end))?;
+        builder
+            .reveal_recv(&(*start..*end))
+            .map_err(|e| ZkpError::Presentation(e.to_string()))?;
     }
     debug!(
-        "Configured revelations: header + {} field ranges",
+        "Configured revelations: {} sent ranges + {} field ranges",
+        sent_ranges.len(),
         field_ranges.len()
     );
 
-    let transcript_proof = builder.build()?;
+    let transcript_proof = builder
+        .build()
+        .map_err(|e| ZkpError::Presentation(e.to_string()))?;
     let crypto_provider = CryptoProvider::default();
     let mut builder = attestation.presentation_builder(&crypto_provider);
     builder
         .identity_proof(secrets.identity_proof())
         .transcript_proof(transcript_proof);
-    let presentation: Presentation = builder.build()?;
+    let presentation = builder
+        .build()
+        .map_err(|e| ZkpError::Presentation(e.to_string()))?;
     debug!("Presentation built successfully");
 
-    file_io::save_file(&provider, "presentation", &presentation).await?;
+    Ok(presentation)
+}
+
+/// Ranges a presentation reveals: the request line and Host header, then the payment
+/// fields and nonce echo, limited to `reveal_fields` when any are given and to the
+/// non-opt-in ones otherwise. Fails when a field `server_config` requires is left out.
+fn presentation_reveal_ranges(
+    sent: &[u8],
+    received: &[u8],
+    provider: &Provider,
+    server_config: &ServerConfig,
+    reveal_fields: &[String],
+) -> Result<TranscriptRanges, ZkpError> {
+    // HTTP/2 headers are compressed and were never committed, so nothing sent is revealed
+    let (sent_ranges, mut fields) = if text_parser::is_http2(sent) {
+        (
+            Vec::new(),
+            text_parser::find_http2_fields(received, provider),
+        )
+    } else {
+        let header_range = text_parser::find_host_header_range(sent).ok_or_else(|| {
+            ZkpError::Presentation("Host header not found in sent transcript".into())
+        })?;
+        let request_line_range = text_parser::find_request_line_range(sent).ok_or_else(|| {
+            ZkpError::Presentation("Request line not found in sent transcript".into())
+        })?;
+
+        let transaction_id = text_parser::request_transaction_id(sent);
+        let mut fields = text_parser::find_step_fields(
+            received,
+            provider,
+            transaction_id.as_deref(),
+            &provider.request_steps(),
+        );
+        fields.extend(nonce_echo(received, server_config));
+        (vec![request_line_range, header_range], fields)
+    };
+    for name in reveal_fields {
+        if !fields.iter().any(|field| field.name == name) {
+            return Err(ZkpError::FieldNotFound(name.clone()));
+        }
+    }
+    if reveal_fields.is_empty() {
+        let opt_in = utils::patterns::get_opt_in_fields(provider);
+        fields.retain(|field| !opt_in.contains(&field.name));
+    } else {
+        fields.retain(|field| reveal_fields.iter().any(|name| name == field.name));
+    }
+    text_parser::check_disclosure(&fields, &server_config.required_fields)?;
+    let field_ranges = text_parser::coalesce_ranges(
+        received,
+        &text_parser::field_ranges(&fields)?,
+        server_config.range_merge_gap,
+    );
+    text_parser::check_range_bounds("Sent", &sent_ranges, sent.len(), &[])?;
+    text_parser::check_range_bounds("Received", &field_ranges, received.len(), &fields)?;
+
+    Ok((sent_ranges, field_ranges))
+}
+
+/// The server's echo of the nonce, when `server_config` has a `nonce_echo_pattern`
+fn nonce_echo(received: &[u8], server_config: &ServerConfig) -> Option<text_parser::FieldMatch> {
+    let pattern = server_config.nonce_echo_pattern.as_deref()?;
+    text_parser::find_nonce_echo(received, pattern)
+}
+
+async fn save_presentation(
+    provider: &Provider,
+    transaction_id: Option<&str>,
+    presentation: &Presentation,
+    format: ArtifactFormat,
+) -> Result<(), ZkpError> {
+    file_io::save_file(
+        provider,
+        transaction_id,
+        "presentation",
+        presentation,
+        format,
+    )
+    .await?;
     debug!("Presentation saved to disk");
 
+    // Absent for attestations saved before certificates were recorded
+    let certificate = file_io::load_file(provider, transaction_id, "certificate", format)
+        .unwrap_or_else(|e| {
+            debug!("No saved server certificate: {}", e);
+            None
+        });
+    let bundle = bundle_presentation(provider, transaction_id, presentation, certificate)?;
+    let bundle_path =
+        file_io::get_transaction_file_path(&provider.to_string(), transaction_id, "bundle");
+    file_io::save_bundle(&bundle_path, &bundle, format).await?;
+
     info!("Presentation completed and saved");
     info!("Next: Run verification with 'cargo run --release --bin tlsn-verify'");
 
     Ok(())
 }
 
-pub async fn verify(url: &str, unauthed_bytes: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// Stable identifier of a presentation: the hex SHA-256 of its canonical encoding,
+/// covering the attestation and the revealed ranges. Verifiers can use it to spot the
+/// same proof submitted twice, whichever artifact format it arrived in.
+pub fn presentation_fingerprint(presentation: &Presentation) -> Result<String, ZkpError> {
+    file_io::fingerprint(presentation)
+}
+
+/// Packages a presentation with its provider, notary key, session time and the
+/// server certificate seen before notarizing
+fn bundle_presentation(
+    provider: &Provider,
+    transaction_id: Option<&str>,
+    presentation: &Presentation,
+    certificate: Option<CertificateInfo>,
+) -> Result<PresentationBundle, ZkpError> {
+    let notary_key = hex::encode(&presentation.verifying_key().data);
+    let output = presentation
+        .clone()
+        .verify(&CryptoProvider::default())
+        .map_err(|e| ZkpError::Presentation(e.to_string()))?;
+
+    Ok(PresentationBundle {
+        provider: provider.to_string(),
+        transaction_id: transaction_id.map(str::to_string),
+        notary_key,
+        session_time: chrono::DateTime::UNIX_EPOCH
+            + std::time::Duration::from_secs(output.connection_info.time),
+        certificate,
+        presentation: presentation.clone(),
+    })
+}
+
+/// Verifies either a bundle or the saved presentation for the provider at `url`,
+/// whichever is given. Shared by the verify binary and the prove binary's verify mode.
+pub async fn verify_artifact(
+    url: Option<&str>,
+    bundle: Option<&str>,
+    unauthed_bytes: &str,
+    format: ArtifactFormat,
+    options: &VerifyOptions,
+) -> Result<VerificationReport, Box<dyn std::error::Error>> {
+    match (bundle, url) {
+        (Some(bundle), _) => verify_bundle(bundle, unauthed_bytes, format, options).await,
+        (None, Some(url)) => verify(url, unauthed_bytes, format, options).await,
+        (None, None) => Err("Either a URL or a bundle is required to verify".into()),
+    }
+}
+
+/// Verifies a presentation bundle, checking its metadata against the presentation.
+///
+/// The provider must match the proven server name and a transaction id must match the
+/// revealed request line. Only the certificate details stay prover-asserted.
+pub async fn verify_bundle(
+    path: &str,
+    unauthed_bytes: &str,
+    format: ArtifactFormat,
+    options: &VerifyOptions,
+) -> Result<VerificationReport, Box<dyn std::error::Error>> {
+    use clap::ValueEnum;
+
+    let bundle = file_io::load_bundle(path, format)?;
+    let provider = Provider::from_str(&bundle.provider, true)
+        .map_err(|_| format!("Unknown provider {:?} in bundle", bundle.provider))?;
+
+    if hex::encode(&bundle.presentation.verifying_key().data) != bundle.notary_key {
+        return Err(ZkpError::Presentation(
+            "Bundle notary key does not match the presentation".into(),
+        )
+        .into());
+    }
+
+    let mut report = verify_presentation(
+        Some(&provider),
+        bundle.presentation,
+        unauthed_bytes,
+        options,
+    )?;
+    if report.session_time != bundle.session_time {
+        return Err(ZkpError::Presentation(
+            "Bundle session time does not match the presentation".into(),
+        )
+        .into());
+    }
+    if let Some(transaction_id) = &bundle.transaction_id {
+        report.check_transaction_id(transaction_id)?;
+    }
+    if let Some(certificate) = &bundle.certificate {
+        if !certificate.valid_at(report.session_time) {
+            return Err(ZkpError::Presentation(
+                "Bundle certificate was not valid at the session time".into(),
+            )
+            .into());
+        }
+        info!(
+            "Server certificate: {} issued by {}",
+            certificate.subject, certificate.issuer
+        );
+    }
+    report.certificate = bundle.certificate;
+
+    Ok(report)
+}
+
+/// Verifies the saved presentation for the provider at `url`, applying the policy
+/// checks in `options` on top of cryptographic verification. A `url` naming a
+/// transaction selects the presentation saved for it.
+pub async fn verify(
+    url: &str,
+    unauthed_bytes: &str,
+    format: ArtifactFormat,
+    options: &VerifyOptions,
+) -> Result<VerificationReport, Box<dyn std::error::Error>> {
     let provider = utils::text_parser::parse_provider_from_url(url);
+    let transaction_id = text_parser::url_transaction_id(url);
+    let path = file_io::get_transaction_file_path(
+        &provider.to_string(),
+        transaction_id.as_deref(),
+        "presentation",
+    );
+    let bytes = std::fs::read(&path).map_err(ZkpError::from)?;
+
+    verify_bytes(Some(&provider), &bytes, unauthed_bytes, format, options)
+}
+
+/// Verifies a serialized presentation held in memory.
+///
+/// Touches neither the filesystem nor an async runtime, so it also serves callers
+/// without either, such as the `wasm` feature's browser entry point. Without a
+/// `provider` it is detected from the presentation's server name.
+pub fn verify_bytes(
+    provider: Option<&Provider>,
+    bytes: &[u8],
+    unauthed_bytes: &str,
+    format: ArtifactFormat,
+    options: &VerifyOptions,
+) -> Result<VerificationReport, Box<dyn std::error::Error>> {
+    let presentation: Presentation = file_io::decode_artifact_as(bytes, format)?;
+    verify_presentation(provider, presentation, unauthed_bytes, options)
+}
+
+/// Verifies the saved presentation of each transaction, continuing past failures.
+///
+/// Results are in the same order as `transaction_ids`. A presentation whose revealed
+/// request line names a different transaction is rejected.
+pub async fn verify_batch(
+    provider: &Provider,
+    transaction_ids: &[String],
+    unauthed_bytes: &str,
+    format: ArtifactFormat,
+    options: &VerifyOptions,
+) -> Vec<Result<VerificationReport, ZkpError>> {
+    transaction_ids
+        .iter()
+        .map(|transaction_id| {
+            info!("Verifying transaction {}", transaction_id);
+            verify_transaction(provider, transaction_id, unauthed_bytes, format, options)
+        })
+        .collect()
+}
 
-    let presentation_path = file_io::get_file_path(&provider.to_string(), "presentation");
+fn verify_transaction(
+    provider: &Provider,
+    transaction_id: &str,
+    unauthed_bytes: &str,
+    format: ArtifactFormat,
+    options: &VerifyOptions,
+) -> Result<VerificationReport, ZkpError> {
+    let presentation: Presentation =
+        file_io::load_file(provider, Some(transaction_id), "presentation", format)?;
+    let report = verify_presentation(Some(provider), presentation, unauthed_bytes, options)
+        .map_err(|e| match e.downcast::<ZkpError>() {
+            Ok(e) => *e,
+            Err(e) => ZkpError::Presentation(e.to_string()),
+        })?;
 
+    // Batches tolerate an unrevealed request line, which HTTP/2 presentations lack
+    if report.transaction_id.is_some() {
+        report.check_transaction_id(transaction_id)?;
+    }
+    Ok(report)
+}
+
+fn verify_presentation(
+    provider: Option<&Provider>,
+    presentation: Presentation,
+    unauthed_bytes: &str,
+    options: &VerifyOptions,
+) -> Result<VerificationReport, Box<dyn std::error::Error>> {
     use std::time::Duration;
-    use tlsn_core::{
-        presentation::{Presentation, PresentationOutput},
-        signing::VerifyingKey,
-    };
+    use tlsn_core::{presentation::PresentationOutput, signing::VerifyingKey};
 
     info!("🔍 Verifying transaction presentation...");
+    let &[unauthed] = unauthed_bytes.as_bytes() else {
+        return Err(ZkpError::Presentation(format!(
+            "unauthed_bytes must be a single ASCII character, got {:?}",
+            unauthed_bytes
+        ))
+        .into());
+    };
+    let fingerprint = presentation_fingerprint(&presentation)?;
+    info!("Presentation fingerprint: {}", fingerprint);
 
-    let presentation: Presentation = bincode::deserialize(&std::fs::read(presentation_path)?)?;
     let VerifyingKey {
         alg,
         data: key_data,
     } = presentation.verifying_key();
 
-    utils::info::print_notary_info(alg, hex::encode(key_data));
+    let notary_key = hex::encode(key_data);
+    utils::info::print_notary_info(alg, &notary_key);
+    if !options.trusted_notary_keys.is_empty()
+        && !options
+            .trusted_notary_keys
+            .iter()
+            .any(|trusted| trusted.trim().eq_ignore_ascii_case(&notary_key))
+    {
+        return Err(ZkpError::UntrustedNotary(notary_key).into());
+    }
 
     let PresentationOutput {
         server_name,
@@ -222,19 +1084,175 @@ pub async fn verify(url: &str, unauthed_bytes: &str) -> Result<(), Box<dyn std::
         .verify(&CryptoProvider::default())
         .map_err(|e| format!("Cryptographic verification failed: {}", e))?;
 
-    let mut partial_transcript = transcript.unwrap();
-    partial_transcript.set_unauthed(unauthed_bytes.as_bytes()[0]);
+    let mut partial_transcript = transcript.ok_or_else(|| {
+        ZkpError::Presentation("Presentation does not include a transcript".to_string())
+    })?;
+    partial_transcript.set_unauthed(unauthed);
 
-    utils::info::print_provider_info(
-        &server_name.unwrap(),
-        chrono::DateTime::UNIX_EPOCH + Duration::from_secs(connection_info.time),
-    );
+    let server_name = server_name.ok_or_else(|| {
+        ZkpError::Presentation("Presentation does not disclose the server name".to_string())
+    })?;
+    if !options.allowed_server_names.is_empty()
+        && !options
+            .allowed_server_names
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(server_name.as_str()))
+    {
+        return Err(ZkpError::UnexpectedServer(server_name.to_string()).into());
+    }
+    let provider = &Provider::resolve(
+        provider,
+        server_name.as_str(),
+        &options.allowed_server_names,
+    )?;
+    let session_time = chrono::DateTime::UNIX_EPOCH + Duration::from_secs(connection_info.time);
+    utils::info::print_provider_info(&server_name, session_time);
+
+    if options.offline {
+        debug!("Offline verification: skipping presentation age checks");
+    } else if let Some(max_age) = options.max_age {
+        let age = (chrono::Utc::now() - session_time)
+            .to_std()
+            .unwrap_or_default();
+        if age > max_age {
+            return Err(ZkpError::StalePresentation {
+                age_secs: age.as_secs(),
+                max_age_secs: max_age.as_secs(),
+            }
+            .into());
+        }
+    }
 
     utils::info::print_verification_results(
-        &partial_transcript.sent_unsafe(),
-        &partial_transcript.received_unsafe(),
-        &provider,
+        partial_transcript.sent_unsafe(),
+        partial_transcript.received_unsafe(),
+        provider,
     );
 
-    Ok(())
+    let received_revealed = utils::index_ranges(partial_transcript.received_authed());
+    text_parser::check_content_length(partial_transcript.received_unsafe(), &received_revealed)?;
+    let fields = text_parser::find_revealed_fields(
+        partial_transcript.received_unsafe(),
+        &received_revealed,
+        provider,
+        options
+            .nonce_echo_patterns
+            .get(provider)
+            .map(String::as_str),
+    )
+    .into_iter()
+    .map(|field| VerifiedField {
+        name: field.name.to_string(),
+        range: (field.start, field.end),
+        raw: field.text,
+        value: field.value,
+        proven: field.proven,
+    })
+    .collect();
+
+    let mut report = VerificationReport::new(server_name.to_string(), session_time, fields);
+    report.set_raw_bytes(partial_transcript.received_unsafe(), &received_revealed);
+    report.fingerprint = Some(fingerprint);
+    // The attestation fixes the transcript lengths, so every byte outside the revealed
+    // ranges was notarized and deliberately hidden rather than missing
+    let sent_coverage = utils::DisclosureCoverage::new(
+        partial_transcript.len_sent(),
+        &utils::index_ranges(partial_transcript.sent_authed()),
+    );
+    let received_coverage =
+        utils::DisclosureCoverage::new(partial_transcript.len_received(), &received_revealed);
+    utils::print_disclosure_coverage("Sent", &sent_coverage);
+    utils::print_disclosure_coverage("Received", &received_coverage);
+    debug!(
+        "Received transcript as revealed:\n{}",
+        utils::render_redacted_transcript(&partial_transcript, '*')
+    );
+    report.redacted_sent = sent_coverage.redacted;
+    report.redacted_received = received_coverage.redacted;
+    report.transaction_id = text_parser::request_transaction_id(partial_transcript.sent_unsafe());
+    match &report.transaction_id {
+        Some(transaction_id) => info!("Revealed request is for transaction {}", transaction_id),
+        None => warn!("Request line was not revealed; the transaction id is unproven"),
+    }
+    if let Some(expected) = &options.expected_transaction_id {
+        report.check_transaction_id(expected)?;
+    }
+    if let Some(expected) = &options.expected_nonce {
+        report.check_nonce(expected)?;
+    }
+    if let Some(settled_states) = options.settled_states.get(provider) {
+        let state = report.field(provider.status_field());
+        domain::check_settled(state.map(|field| field.value.as_str()), settled_states)?;
+    }
+    if let Some((amount_field, currency_field)) = utils::patterns::get_payment_fields(provider) {
+        report.normalize_payment(amount_field, currency_field);
+    }
+    if let Some((fee_field, rate_field)) = utils::patterns::get_conversion_fields(provider) {
+        report.normalize_conversion(fee_field, rate_field);
+        if let Some(rate) = report.exchange_rate {
+            info!("Revealed exchange rate {} (fee {:?})", rate, report.fee);
+        }
+    }
+    for warning in &report.warnings {
+        warn!("{}", warning);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opt_in_fields_revealed_only_when_named() {
+        let sent = b"GET /gateway/v3/profiles/1/transfers/987 HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        let received = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"id":987,"state":"OUTGOING_PAYMENT_SENT","sourceAmount":110.42,"fee":1.12,"#,
+            r#""targetAmount":100.57,"targetCurrency":"EUR"}"#
+        )
+        .as_bytes();
+        let server = ServerConfig::new("wise.com", 443);
+        let revealed = |reveal_fields: &[String]| {
+            let (_, ranges) =
+                presentation_reveal_ranges(sent, received, &Provider::Wise, &server, reveal_fields)
+                    .unwrap();
+            utils::info::render_redacted(received, &ranges, '*')
+        };
+
+        let default = revealed(&[]);
+        assert!(default.contains(r#""targetAmount":100.57"#));
+        assert!(!default.contains("110.42"));
+        assert!(!default.contains("1.12"));
+
+        let named = revealed(&["targetAmount".to_string(), "fee".to_string()]);
+        assert!(named.contains(r#""fee":1.12"#));
+        assert!(!named.contains("110.42"));
+    }
+
+    #[test]
+    fn test_nonce_echo_and_required_fields_follow_server_config() {
+        let sent = b"GET /gateway/v3/profiles/1/transfers/987 HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        let received = concat!(
+            "HTTP/1.1 200 OK\r\nX-Request-Id: c0ffee-42\r\n\r\n",
+            r#"{"id":987,"state":"OUTGOING_PAYMENT_SENT","targetAmount":100.57}"#
+        )
+        .as_bytes();
+        let mut server = ServerConfig {
+            required_fields: vec!["nonce".to_string()],
+            ..ServerConfig::new("wise.com", 443)
+        };
+        assert!(matches!(
+            presentation_reveal_ranges(sent, received, &Provider::Wise, &server, &[]),
+            Err(ZkpError::InsufficientDisclosure { .. })
+        ));
+
+        server.nonce_echo_pattern = Some("(?i)x-request-id: ([a-z0-9-]+)".to_string());
+        let (_, ranges) =
+            presentation_reveal_ranges(sent, received, &Provider::Wise, &server, &[]).unwrap();
+        let revealed = utils::info::render_redacted(received, &ranges, '*');
+        assert!(revealed.contains("X-Request-Id: c0ffee-42"));
+        assert!(revealed.contains(r#""targetAmount":100.57"#));
+    }
 }
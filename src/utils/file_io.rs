@@ -1,19 +1,419 @@
-use serde::Serialize;
-use tracing::debug;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use std::sync::{Mutex, PoisonError};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
 
-use crate::domain::Provider;
+use crate::domain::{ArtifactFormat, PresentationBundle, Provider};
+use crate::error::ZkpError;
+
+/// Magic bytes prefixed to every saved artifact
+const ARTIFACT_MAGIC: &[u8; 4] = b"ZKPT";
+
+/// Artifact format version; bump whenever the tlsn dependency changes its serialization
+pub const ARTIFACT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = ARTIFACT_MAGIC.len() + 2;
+
+// Temp files with a write in flight; an interrupted write leaves its path behind.
+// These registries only hold paths, so a poisoned lock is recovered rather than
+// panicking on the FFI and Ctrl-C paths that reach them.
+static PENDING_WRITES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// Artifacts fully written since the last `take_written_artifacts`
+static WRITTEN_ARTIFACTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
 pub fn get_file_path(provider: &str, content_type: &str) -> String {
     format!("{}.{}.tlsn", provider, content_type)
 }
 
+/// Path of an artifact, keyed by transaction id when one is given
+pub fn get_transaction_file_path(
+    provider: &str,
+    transaction_id: Option<&str>,
+    content_type: &str,
+) -> String {
+    match transaction_id {
+        Some(transaction_id) => format!(
+            "{}.{}.{}.tlsn",
+            provider,
+            file_name_segment(transaction_id),
+            content_type
+        ),
+        None => get_file_path(provider, content_type),
+    }
+}
+
+/// Percent-encodes everything but ASCII letters, digits, `-` and `_`, so a decoded
+/// transaction id can't add directories, `..` segments or control bytes to a path
+fn file_name_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Serializes an artifact behind a versioned header
+pub fn encode_artifact<T: Serialize>(content: &T) -> Result<Vec<u8>, ZkpError> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend_from_slice(ARTIFACT_MAGIC);
+    bytes.extend_from_slice(&ARTIFACT_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, content)?;
+    Ok(bytes)
+}
+
+/// Hex SHA-256 of an artifact's bincode encoding, without the versioned header.
+///
+/// Bincode encodes the same value to the same bytes every time, so the hash is stable
+/// across runs, machines and the format the artifact was saved in.
+pub fn fingerprint<T: Serialize>(content: &T) -> Result<String, ZkpError> {
+    Ok(hex::encode(Sha256::digest(bincode::serialize(content)?)))
+}
+
+/// Deserializes an artifact after checking its header.
+///
+/// Files without a header predate versioning and are reported as version 0.
+pub fn decode_artifact<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ZkpError> {
+    let found = match bytes.strip_prefix(ARTIFACT_MAGIC.as_slice()) {
+        Some(rest) if rest.len() >= 2 => u16::from_le_bytes([rest[0], rest[1]]),
+        _ => 0,
+    };
+    if found != ARTIFACT_VERSION {
+        return Err(ZkpError::VersionMismatch {
+            found,
+            expected: ARTIFACT_VERSION,
+        });
+    }
+
+    Ok(bincode::deserialize(&bytes[HEADER_LEN..])?)
+}
+
+/// JSON artifacts carry their version inline instead of a binary header
+#[derive(Serialize, Deserialize)]
+struct JsonArtifact<T> {
+    version: u16,
+    content: T,
+}
+
+/// Serializes an artifact in the requested format
+pub fn encode_artifact_as<T: Serialize>(
+    content: &T,
+    format: ArtifactFormat,
+) -> Result<Vec<u8>, ZkpError> {
+    match format {
+        ArtifactFormat::Bincode => encode_artifact(content),
+        ArtifactFormat::Json => Ok(serde_json::to_vec_pretty(&JsonArtifact {
+            version: ARTIFACT_VERSION,
+            content,
+        })?),
+    }
+}
+
+/// Deserializes an artifact saved in the given format
+pub fn decode_artifact_as<T: DeserializeOwned>(
+    bytes: &[u8],
+    format: ArtifactFormat,
+) -> Result<T, ZkpError> {
+    match format {
+        ArtifactFormat::Bincode => decode_artifact(bytes),
+        ArtifactFormat::Json => {
+            let artifact: JsonArtifact<T> = serde_json::from_slice(bytes)?;
+            if artifact.version != ARTIFACT_VERSION {
+                return Err(ZkpError::VersionMismatch {
+                    found: artifact.version,
+                    expected: ARTIFACT_VERSION,
+                });
+            }
+            Ok(artifact.content)
+        }
+    }
+}
+
 pub async fn save_file<T: Serialize>(
     provider: &Provider,
+    transaction_id: Option<&str>,
     content_type: &str,
     content: &T,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let path = get_file_path(&provider.to_string(), content_type);
-    tokio::fs::write(&path, bincode::serialize(content)?).await?;
-    debug!("Saved {} to {}", content_type, path);
+    format: ArtifactFormat,
+) -> Result<(), ZkpError> {
+    let path = get_transaction_file_path(&provider.to_string(), transaction_id, content_type);
+    write_artifact(&path, encode_artifact_as(content, format)?).await?;
+    debug!("Saved {} to {} as {:?}", content_type, path, format);
     Ok(())
 }
+
+pub fn load_file<T: DeserializeOwned>(
+    provider: &Provider,
+    transaction_id: Option<&str>,
+    content_type: &str,
+    format: ArtifactFormat,
+) -> Result<T, ZkpError> {
+    let path = get_transaction_file_path(&provider.to_string(), transaction_id, content_type);
+    let content = decode_artifact_as(&std::fs::read(&path)?, format)?;
+    debug!("Loaded {} from {}", content_type, path);
+    Ok(content)
+}
+
+pub async fn save_bundle(
+    path: &str,
+    bundle: &PresentationBundle,
+    format: ArtifactFormat,
+) -> Result<(), ZkpError> {
+    write_artifact(path, encode_artifact_as(bundle, format)?).await?;
+    debug!("Saved presentation bundle to {}", path);
+    Ok(())
+}
+
+/// Writes an artifact atomically: the bytes go to a temp file in the same directory,
+/// which is renamed over `path` only once fully written, so readers never see a
+/// truncated artifact.
+async fn write_artifact(path: &str, bytes: Vec<u8>) -> Result<(), ZkpError> {
+    let temp_path = format!("{}.{}.tmp", path, std::process::id());
+    PENDING_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(temp_path.clone());
+
+    let result = write_and_rename(&temp_path, path, &bytes).await;
+    PENDING_WRITES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|pending| *pending != temp_path);
+    match result {
+        Ok(()) => WRITTEN_ARTIFACTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(path.to_string()),
+        Err(_) => {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+    }
+    Ok(result?)
+}
+
+/// Returns the paths of artifacts written since the previous call, in write order,
+/// and forgets them
+pub fn take_written_artifacts() -> Vec<String> {
+    std::mem::take(
+        &mut *WRITTEN_ARTIFACTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    )
+}
+
+async fn write_and_rename(temp_path: &str, path: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::File::create(temp_path).await?;
+    file.write_all(bytes).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(temp_path, path).await
+}
+
+/// Deletes artifacts whose write never completed, e.g. after Ctrl-C.
+/// Returns how many files were removed.
+pub fn remove_partial_artifacts() -> usize {
+    let pending = std::mem::take(
+        &mut *PENDING_WRITES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    );
+    pending
+        .iter()
+        .filter(|path| match std::fs::remove_file(path) {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => {
+                warn!("Failed to remove partial artifact {}: {}", path, e);
+                false
+            }
+        })
+        .count()
+}
+
+/// Overwrites the secrets file with zeros and deletes it, once a presentation for the
+/// same transaction exists. Returns whether a secrets file was removed.
+///
+/// The overwrite is best effort: copy-on-write filesystems and SSD wear leveling may
+/// keep the old blocks around, so this narrows the exposure rather than guaranteeing it.
+pub async fn shred_secrets(
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Result<bool, ZkpError> {
+    let provider = provider.to_string();
+    shred_file(
+        &get_transaction_file_path(&provider, transaction_id, "secrets"),
+        &get_transaction_file_path(&provider, transaction_id, "presentation"),
+    )
+    .await
+}
+
+async fn shred_file(path: &str, required_path: &str) -> Result<bool, ZkpError> {
+    if !tokio::fs::try_exists(required_path).await? {
+        return Err(ZkpError::Presentation(format!(
+            "{} not found; keeping {} so a presentation can still be built",
+            required_path, path
+        )));
+    }
+
+    let len = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.write_all(&vec![0u8; len as usize]).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::remove_file(path).await?;
+    debug!("Overwrote and removed {}", path);
+    Ok(true)
+}
+
+pub fn load_bundle(path: &str, format: ArtifactFormat) -> Result<PresentationBundle, ZkpError> {
+    let bundle = decode_artifact_as(&std::fs::read(path)?, format)?;
+    debug!("Loaded presentation bundle from {}", path);
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifact_roundtrip() {
+        let bytes = encode_artifact(&vec![1u8, 2, 3]).unwrap();
+        assert!(bytes.starts_with(ARTIFACT_MAGIC));
+
+        let decoded: Vec<u8> = decode_artifact(&bytes).unwrap();
+        assert_eq!(decoded, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable_and_content_bound() {
+        let expected = fingerprint(&vec![1u8, 2, 3]).unwrap();
+        assert_eq!(expected.len(), 64);
+        assert_eq!(fingerprint(&vec![1u8, 2, 3]).unwrap(), expected);
+        assert_ne!(fingerprint(&vec![1u8, 2, 4]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_json_artifact_roundtrip() {
+        let bytes = encode_artifact_as(&vec![1u8, 2, 3], ArtifactFormat::Json).unwrap();
+        assert!(
+            String::from_utf8(bytes.clone())
+                .unwrap()
+                .contains("\"version\"")
+        );
+
+        let decoded: Vec<u8> = decode_artifact_as(&bytes, ArtifactFormat::Json).unwrap();
+        assert_eq!(decoded, vec![1, 2, 3]);
+        assert!(decode_artifact_as::<Vec<u8>>(&bytes, ArtifactFormat::Bincode).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_artifact_replaces_atomically() {
+        let dir = std::env::temp_dir().join(format!("zkp2p-file-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wise.attestation.tlsn");
+        let path = path.to_str().unwrap();
+
+        std::fs::write(path, b"old").unwrap();
+        write_artifact(path, b"new".to_vec()).await.unwrap();
+
+        assert_eq!(std::fs::read(path).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        // Other tests may write concurrently, so only this path is checked
+        assert!(
+            take_written_artifacts()
+                .iter()
+                .any(|written| written == path)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_secrets_shredded_only_after_presentation() {
+        let dir = std::env::temp_dir().join(format!("zkp2p-shred-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let secrets = dir.join("wise.secrets.tlsn");
+        let presentation = dir.join("wise.presentation.tlsn");
+        let (secrets, presentation) = (secrets.to_str().unwrap(), presentation.to_str().unwrap());
+
+        std::fs::write(secrets, b"secret").unwrap();
+        assert!(shred_file(secrets, presentation).await.is_err());
+        assert!(std::fs::exists(secrets).unwrap());
+
+        std::fs::write(presentation, b"presentation").unwrap();
+        assert!(shred_file(secrets, presentation).await.unwrap());
+        assert!(!std::fs::exists(secrets).unwrap());
+        assert!(!shred_file(secrets, presentation).await.unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_artifact_saved_under_requested_transaction() {
+        let transaction_id = format!("roundtrip-{}", std::process::id());
+        let url = format!(
+            "https://wise.com/gateway/v3/profiles/1/transfers/{}",
+            transaction_id
+        );
+        let saved_as = crate::utils::text_parser::url_transaction_id(&url);
+        assert_eq!(saved_as.as_deref(), Some(transaction_id.as_str()));
+        assert_eq!(
+            crate::utils::text_parser::url_transaction_id("wise.com"),
+            None
+        );
+
+        let provider = Provider::Wise;
+        save_file(
+            &provider,
+            saved_as.as_deref(),
+            "presentation",
+            &vec![7u8],
+            ArtifactFormat::Bincode,
+        )
+        .await
+        .unwrap();
+        let path = get_transaction_file_path("wise", Some(&transaction_id), "presentation");
+        let loaded: Result<Vec<u8>, _> = load_file(
+            &provider,
+            Some(&transaction_id),
+            "presentation",
+            ArtifactFormat::Bincode,
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn test_transaction_file_path_escapes_id() {
+        assert_eq!(
+            get_transaction_file_path("wise", Some("123456789"), "attestation"),
+            "wise.123456789.attestation.tlsn"
+        );
+        assert_eq!(
+            get_transaction_file_path("wise", Some("tx 42/é"), "attestation"),
+            "wise.tx%2042%2F%C3%A9.attestation.tlsn"
+        );
+        assert_eq!(
+            get_transaction_file_path("wise", Some("../..\\x\n"), "secrets"),
+            "wise.%2E%2E%2F%2E%2E%5Cx%0A.secrets.tlsn"
+        );
+    }
+
+    #[test]
+    fn test_unversioned_artifact_rejected() {
+        let legacy = bincode::serialize(&vec![1u8, 2, 3]).unwrap();
+
+        match decode_artifact::<Vec<u8>>(&legacy) {
+            Err(ZkpError::VersionMismatch { found, expected }) => {
+                assert_eq!(found, 0);
+                assert_eq!(expected, ARTIFACT_VERSION);
+            }
+            other => panic!("expected version mismatch, got {:?}", other),
+        }
+    }
+}
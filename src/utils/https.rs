@@ -0,0 +1,600 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use hyper::{HeaderMap, StatusCode, body::Bytes};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{
+        ClientConfig, DEFAULT_VERSIONS, RootCertStore, SupportedProtocolVersion, crypto::ring,
+        pki_types::ServerName, version,
+    },
+};
+use tracing::{debug, info};
+
+use crate::domain::{CertificateInfo, ServerConfig, TlsVersion};
+use crate::error::ZkpError;
+use crate::utils::net::{connect_tcp, connect_tcp_system};
+use crate::utils::tls::{ConnectionMode, build_request};
+
+/// Response of a plain HTTPS request
+#[derive(Debug)]
+pub struct PlainResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+/// Longest `Retry-After` a pre-flight request waits out before giving up
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Performs an ordinary HTTPS GET outside of MPC-TLS.
+///
+/// Nothing sent or received here is notarized; use it only for pre-flight lookups.
+/// A 429 is retried up to `server.rate_limit_retries` times after waiting out its
+/// `Retry-After`, then fails with `ZkpError::RateLimited`.
+pub async fn plain_get(
+    server: &ServerConfig,
+    url: &str,
+    extra_headers: &[(&str, &str)],
+    description: &str,
+    user_agent: &str,
+    max_body_len: usize,
+) -> Result<PlainResponse> {
+    let mut retries = server.rate_limit_retries;
+
+    loop {
+        let socket = connect_tcp(&server.host, server.port, server.doh_url.as_deref())
+            .await
+            .with_context(|| format!("Failed to connect to {}:{}", server.host, server.port))?;
+        let response = tls_get(
+            socket,
+            server,
+            url,
+            extra_headers,
+            description,
+            user_agent,
+            max_body_len,
+        )
+        .await?;
+        if response.status != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(response);
+        }
+
+        let wait = retry_after(&response.headers);
+        match wait.filter(|wait| retries > 0 && *wait <= MAX_RETRY_AFTER) {
+            Some(wait) => {
+                retries -= 1;
+                info!(
+                    "{} is rate limited, retrying in {}s",
+                    server.host,
+                    wait.as_secs()
+                );
+                tokio::time::sleep(wait).await;
+            }
+            None => {
+                return Err(ZkpError::RateLimited {
+                    retry_after: wait.map(|wait| wait.as_secs()),
+                }
+                .into());
+            }
+        }
+    }
+}
+
+/// Delay a `Retry-After` header asks for, given as seconds or as an HTTP date
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(hyper::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// User-Agent for DoH queries; the resolver learns nothing about the proving client
+const DOH_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Largest DoH JSON answer accepted
+const MAX_DOH_RESPONSE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    data: String,
+}
+
+/// Resolves `host` with the JSON DNS-over-HTTPS API served by e.g.
+/// `https://1.1.1.1/dns-query` and `https://8.8.8.8/resolve`, returning IPv4 then IPv6
+/// addresses. The resolver's own host goes through system DNS, so an IP literal URL
+/// keeps every lookup off the local resolver.
+pub async fn doh_lookup(doh_url: &str, host: &str) -> Result<Vec<IpAddr>> {
+    let uri: hyper::Uri = doh_url
+        .parse()
+        .with_context(|| format!("Invalid DoH URL {}", doh_url))?;
+    let resolver_host = uri
+        .host()
+        .with_context(|| format!("DoH URL {} has no host", doh_url))?;
+    let resolver = ServerConfig::new(
+        resolver_host.trim_start_matches('[').trim_end_matches(']'),
+        uri.port_u16().unwrap_or(443),
+    );
+
+    let mut addrs = Vec::new();
+    for record_type in ["A", "AAAA"] {
+        let url = format!(
+            "{}?name={}&type={}&ct=application/dns-json",
+            doh_url, host, record_type
+        );
+        let socket = connect_tcp_system(&resolver.host, resolver.port)
+            .await
+            .with_context(|| format!("Failed to connect to DoH resolver {}", resolver.host))?;
+        let response = tls_get(
+            socket,
+            &resolver,
+            &url,
+            &[],
+            "DoH query",
+            DOH_USER_AGENT,
+            MAX_DOH_RESPONSE,
+        )
+        .await?;
+        if !response.status.is_success() {
+            return Err(anyhow::anyhow!(
+                "DoH resolver returned {} for {} {}",
+                response.status,
+                record_type,
+                host
+            ));
+        }
+        addrs.extend(parse_doh_response(&response.body)?);
+    }
+
+    Ok(addrs)
+}
+
+/// Addresses in a DoH JSON answer; CNAME and other non-address records are skipped
+fn parse_doh_response(body: &[u8]) -> Result<Vec<IpAddr>> {
+    let response: DohResponse =
+        serde_json::from_slice(body).context("Invalid DoH JSON response")?;
+    // Status is the DNS RCODE; NXDOMAIN and SERVFAIL carry no usable answer
+    if response.status != 0 {
+        return Err(anyhow::anyhow!(
+            "DoH query failed with RCODE {}",
+            response.status
+        ));
+    }
+
+    Ok(response
+        .answer
+        .iter()
+        .filter_map(|answer| answer.data.parse().ok())
+        .collect())
+}
+
+async fn tls_get(
+    socket: tokio::net::TcpStream,
+    server: &ServerConfig,
+    url: &str,
+    extra_headers: &[(&str, &str)],
+    description: &str,
+    user_agent: &str,
+    max_body_len: usize,
+) -> Result<PlainResponse> {
+    let tls_config = client_config(server)?;
+
+    let server_name = ServerName::try_from(server.sni().to_string())
+        .with_context(|| format!("Invalid server name {}", server.sni()))?;
+    let tls_stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, socket)
+        .await
+        .context("TLS handshake failed")?;
+
+    send_plain_request(
+        tls_stream,
+        server,
+        url,
+        extra_headers,
+        description,
+        user_agent,
+        max_body_len,
+    )
+    .await
+}
+
+/// Handshakes with the server and describes the leaf certificate, if it can be parsed.
+///
+/// The MPC-TLS prover does not expose the chain it validated, so this is a separate
+/// connection made just before notarizing.
+pub async fn inspect_certificate(server: &ServerConfig) -> Result<Option<CertificateInfo>> {
+    let tls_config = client_config(server)?;
+    let server_name = ServerName::try_from(server.sni().to_string())
+        .with_context(|| format!("Invalid server name {}", server.sni()))?;
+    let socket = connect_tcp(&server.host, server.port, server.doh_url.as_deref())
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", server.host, server.port))?;
+    let tls_stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, socket)
+        .await
+        .context("TLS handshake failed")?;
+
+    let chain = tls_stream.get_ref().1.peer_certificates().unwrap_or(&[]);
+    Ok(chain.first().and_then(|leaf| certificate_info(leaf)))
+}
+
+/// Base64 SHA-256 of a DER certificate's SubjectPublicKeyInfo, the value
+/// `openssl x509 -pubkey | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64` prints
+pub fn spki_sha256(cert_der: &[u8]) -> Option<String> {
+    let spki = spki_der(cert_der)?;
+    Some(base64::engine::general_purpose::STANDARD.encode(Sha256::digest(spki)))
+}
+
+/// Locates the SubjectPublicKeyInfo element inside a DER X.509 certificate
+fn spki_der(cert_der: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = der_element(cert_der)?;
+    let (_, tbs_certificate, _) = der_element(certificate)?;
+    let mut fields = tbs_certificate;
+    // Skip the optional [0] version, then serial, signature, issuer, validity and subject
+    if fields.first() == Some(&0xa0) {
+        fields = der_element(fields)?.2;
+    }
+    for _ in 0..5 {
+        fields = der_element(fields)?.2;
+    }
+
+    let (element, _, _) = der_element(fields)?;
+    (element.first() == Some(&0x30)).then_some(element)
+}
+
+/// Reads the subject, issuer, validity and public key of a DER X.509 certificate
+pub fn certificate_info(cert_der: &[u8]) -> Option<CertificateInfo> {
+    let (_, certificate, _) = der_element(cert_der)?;
+    let (_, tbs_certificate, _) = der_element(certificate)?;
+    let mut fields = tbs_certificate;
+    // Skip the optional [0] version, then serial and signature
+    if fields.first() == Some(&0xa0) {
+        fields = der_element(fields)?.2;
+    }
+    for _ in 0..2 {
+        fields = der_element(fields)?.2;
+    }
+
+    let (_, issuer, fields) = der_element(fields)?;
+    let (_, validity, fields) = der_element(fields)?;
+    let (_, subject, _) = der_element(fields)?;
+    let (not_before, _, validity) = der_element(validity)?;
+    let (not_after, _, _) = der_element(validity)?;
+
+    Some(CertificateInfo {
+        subject: distinguished_name(subject),
+        issuer: distinguished_name(issuer),
+        not_before: der_time(not_before)?,
+        not_after: der_time(not_after)?,
+        spki_sha256: spki_sha256(cert_der)?,
+    })
+}
+
+/// Renders the common attributes of a DER Name as `C=US, O=Example, CN=example.com`,
+/// in certificate order
+fn distinguished_name(name: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut sets = name;
+    while let Some((_, set, rest)) = der_element(sets) {
+        sets = rest;
+        let mut attributes = set;
+        while let Some((_, attribute, rest)) = der_element(attributes) {
+            attributes = rest;
+            let Some((_, oid, value)) = der_element(attribute) else {
+                continue;
+            };
+            // id-at attributes are 2.5.4.<n>
+            let label = match oid {
+                [0x55, 0x04, 0x03] => "CN",
+                [0x55, 0x04, 0x06] => "C",
+                [0x55, 0x04, 0x07] => "L",
+                [0x55, 0x04, 0x08] => "ST",
+                [0x55, 0x04, 0x0a] => "O",
+                [0x55, 0x04, 0x0b] => "OU",
+                _ => continue,
+            };
+            if let Some((_, value, _)) = der_element(value) {
+                parts.push(format!("{}={}", label, String::from_utf8_lossy(value)));
+            }
+        }
+    }
+    parts.join(", ")
+}
+
+/// Parses a DER UTCTime or GeneralizedTime element
+fn der_time(element: &[u8]) -> Option<chrono::DateTime<chrono::Utc>> {
+    let (_, contents, _) = der_element(element)?;
+    let text = std::str::from_utf8(contents).ok()?;
+    let text = match element.first()? {
+        // Two-digit years 50-99 are 19xx, per RFC 5280
+        0x17 => format!(
+            "{}{}",
+            if text.get(..2)? >= "50" { "19" } else { "20" },
+            text
+        ),
+        0x18 => text.to_string(),
+        _ => return None,
+    };
+    chrono::NaiveDateTime::parse_from_str(&text, "%Y%m%d%H%M%SZ")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Splits the first DER element off `data` as (whole element, contents, rest)
+fn der_element(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first_len = *data.get(1)?;
+    let (len, header_len) = if first_len < 0x80 {
+        (first_len as usize, 2)
+    } else {
+        let len_bytes = (first_len & 0x7f) as usize;
+        if len_bytes == 0 || len_bytes > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + len_bytes)?
+            .iter()
+            .fold(0usize, |len, byte| len << 8 | *byte as usize);
+        (len, 2 + len_bytes)
+    };
+
+    let end = header_len.checked_add(len)?;
+    let element = data.get(..end)?;
+    Some((element, &element[header_len..], &data[end..]))
+}
+
+/// TLS client configuration for pre-flight requests, honoring the server's
+/// `tls_versions` and `cipher_suites`
+fn client_config(server: &ServerConfig) -> Result<ClientConfig> {
+    let mut provider = ring::default_provider();
+    if !server.cipher_suites.is_empty() {
+        provider.cipher_suites.retain(|suite| {
+            suite
+                .suite()
+                .as_str()
+                .is_some_and(|name| server.cipher_suites.iter().any(|allowed| allowed == name))
+        });
+        if provider.cipher_suites.is_empty() {
+            return Err(anyhow::anyhow!(
+                "None of the cipher_suites {:?} for {} are supported",
+                server.cipher_suites,
+                server.host
+            ));
+        }
+    }
+
+    let versions: Vec<&'static SupportedProtocolVersion> = if server.tls_versions.is_empty() {
+        DEFAULT_VERSIONS.to_vec()
+    } else {
+        server
+            .tls_versions
+            .iter()
+            .map(|tls_version| match tls_version {
+                TlsVersion::Tls12 => &version::TLS12,
+                TlsVersion::Tls13 => &version::TLS13,
+            })
+            .collect()
+    };
+
+    let mut root_store = RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    Ok(ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_protocol_versions(&versions)
+        .with_context(|| {
+            format!(
+                "Unsupported TLS version and cipher suite combination for {}",
+                server.host
+            )
+        })?
+        .with_root_certificates(root_store)
+        .with_no_client_auth())
+}
+
+/// Performs a GET over unencrypted HTTP, e.g. against a local notary without TLS.
+///
+/// Like `plain_get`, nothing here is notarized.
+pub async fn plain_http_get(
+    server: &ServerConfig,
+    url: &str,
+    description: &str,
+    user_agent: &str,
+    max_body_len: usize,
+) -> Result<PlainResponse> {
+    let socket = connect_tcp(&server.host, server.port, server.doh_url.as_deref())
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", server.host, server.port))?;
+
+    send_plain_request(
+        socket,
+        server,
+        url,
+        &[],
+        description,
+        user_agent,
+        max_body_len,
+    )
+    .await
+}
+
+async fn send_plain_request<S>(
+    io: S,
+    server: &ServerConfig,
+    url: &str,
+    extra_headers: &[(&str, &str)],
+    description: &str,
+    user_agent: &str,
+    max_body_len: usize,
+) -> Result<PlainResponse>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut request_sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(io)).await?;
+    tokio::spawn(connection);
+
+    let request = build_request(
+        url,
+        server,
+        extra_headers,
+        description,
+        user_agent,
+        ConnectionMode::Close,
+        server.http_version,
+    )?;
+    let response = request_sender
+        .send_request(request)
+        .await
+        .context("Failed to send request")?;
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = read_body_limited(response.into_body(), max_body_len).await?;
+    debug!("Plain request to {} returned {}", url, status);
+
+    Ok(PlainResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Streams a response body, aborting as soon as it grows past `limit` bytes
+pub async fn read_body_limited<B>(mut body: B, limit: usize) -> Result<Bytes>
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut buffer = Vec::new();
+    while let Some(frame) = body.frame().await {
+        if let Ok(data) = frame?.into_data() {
+            if buffer.len() + data.len() > limit {
+                return Err(ZkpError::ResponseTooLarge { limit }.into());
+            }
+            buffer.extend_from_slice(&data);
+        }
+    }
+
+    Ok(buffer.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_reads_seconds_and_dates() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(hyper::header::RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(retry_after(&headers("30")), Some(Duration::from_secs(30)));
+        // A date already past means no wait
+        assert_eq!(
+            retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_spki_located_in_certificate() {
+        let spki: &[u8] = &[0x30, 0x06, 0x30, 0x00, 0x03, 0x02, 0x00, 0x01];
+        let mut tbs = vec![0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x07];
+        tbs.extend_from_slice(&[0x30, 0x00].repeat(4));
+        tbs.extend_from_slice(spki);
+        let mut cert = vec![0x30, tbs.len() as u8 + 2, 0x30, tbs.len() as u8];
+        cert.extend_from_slice(&tbs);
+
+        assert_eq!(spki_der(&cert), Some(spki));
+        assert_eq!(
+            spki_sha256(&cert),
+            Some(base64::engine::general_purpose::STANDARD.encode(Sha256::digest(spki)))
+        );
+
+        assert_eq!(spki_der(&cert[..cert.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_certificate_info_read_from_certificate() {
+        fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+            let mut element = vec![tag, contents.len() as u8];
+            element.extend_from_slice(contents);
+            element
+        }
+        fn attribute(oid: u8, value: &str) -> Vec<u8> {
+            let mut attribute = der(0x06, &[0x55, 0x04, oid]);
+            attribute.extend(der(0x0c, value.as_bytes()));
+            der(0x31, &der(0x30, &attribute))
+        }
+
+        let issuer = [attribute(0x06, "US"), attribute(0x0a, "Test CA")].concat();
+        let subject = attribute(0x03, "wise.com");
+        let validity = [der(0x17, b"250101000000Z"), der(0x18, b"20260101000000Z")].concat();
+        let tbs = [
+            der(0x02, &[0x07]),
+            der(0x30, &[]),
+            der(0x30, &issuer),
+            der(0x30, &validity),
+            der(0x30, &subject),
+            der(0x30, &[0x30, 0x00, 0x03, 0x02, 0x00, 0x01]),
+        ]
+        .concat();
+        let cert = der(0x30, &der(0x30, &tbs));
+
+        let info = certificate_info(&cert).unwrap();
+        assert_eq!(info.subject, "CN=wise.com");
+        assert_eq!(info.issuer, "C=US, O=Test CA");
+        assert_eq!(info.not_before.to_rfc3339(), "2025-01-01T00:00:00+00:00");
+        assert_eq!(info.not_after.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+        assert_eq!(info.spki_sha256, spki_sha256(&cert).unwrap());
+        assert!(info.valid_at(info.not_before));
+        assert!(!info.valid_at(chrono::DateTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_doh_response_keeps_only_addresses() {
+        let body = br#"{"Status":0,"Answer":[
+            {"name":"wise.com","type":5,"TTL":60,"data":"wise.com.cdn.example."},
+            {"name":"wise.com.cdn.example","type":1,"TTL":60,"data":"104.18.0.1"},
+            {"name":"wise.com.cdn.example","type":28,"TTL":60,"data":"2606:4700::1"}]}"#;
+        let addrs = parse_doh_response(body).unwrap();
+        assert_eq!(
+            addrs,
+            vec![
+                "104.18.0.1".parse::<IpAddr>().unwrap(),
+                "2606:4700::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+
+        assert!(parse_doh_response(br#"{"Status":3}"#).is_err());
+        assert!(parse_doh_response(br#"{"Status":0}"#).unwrap().is_empty());
+    }
+}
@@ -1,13 +1,29 @@
 use color_eyre::eyre::Result;
+use std::io::{self, Write};
+use tlsn_core::transcript::{Idx, PartialTranscript};
 use tracing::{info, warn};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
-use crate::{domain::Provider, utils::text_parser::find_field_ranges};
+use crate::{
+    domain::{Provider, Verbosity, VerificationReport},
+    error::ZkpError,
+    utils::text_parser::find_field_ranges,
+};
 
-pub fn init_tracing() -> Result<()> {
-    let fmt_layer = fmt::layer().compact();
-    let filter_layer = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?;
+/// Installs the subscriber. Logs go to stderr so stdout stays free for scripted output;
+/// `--quiet`/`--verbose` override `RUST_LOG`, which otherwise applies as usual.
+///
+/// `plain` logs in plain ASCII: emoji and other non-ASCII symbols are dropped along
+/// with ANSI colors, for log parsers and terminals that mangle them.
+pub fn init_tracing(verbosity: Verbosity, plain: bool) -> Result<()> {
+    let fmt_layer = fmt::layer()
+        .compact()
+        .with_writer(move || OutputWriter { plain });
+    let filter_layer = match verbosity.filter() {
+        Some(directive) => EnvFilter::try_new(directive)?,
+        None => EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?,
+    };
 
     tracing_subscriber::registry()
         .with(filter_layer)
@@ -18,6 +34,52 @@ pub fn init_tracing() -> Result<()> {
     Ok(())
 }
 
+/// Stderr writer, stripping each event to plain ASCII when `plain` is set. The
+/// formatter hands over each event in a single write, so filtering per write never
+/// splits an escape sequence.
+struct OutputWriter {
+    plain: bool,
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.plain {
+            io::stderr().write_all(plain_text(&String::from_utf8_lossy(buf)).as_bytes())?;
+        } else {
+            io::stderr().write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Drops ANSI escape sequences and non-ASCII characters, along with the space that
+/// separated a dropped symbol from the following word
+fn plain_text(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end at their first letter, e.g. `\x1b[2m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if !c.is_ascii() {
+            if chars.peek() == Some(&' ') && plain.chars().last().is_none_or(char::is_whitespace) {
+                chars.next();
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
 pub fn print_notary_info(alg: impl std::fmt::Display, key_data: impl std::fmt::Display) {
     info!("Cryptographic verification details:");
     info!("Algorithm: {}, Key: {}", alg, key_data);
@@ -31,13 +93,257 @@ pub fn print_provider_info(
     info!("Verified connection: {} at {}", server_name, session_time);
 }
 
+/// Revealed and redacted byte ranges of one transcript direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisclosureCoverage {
+    pub total: usize,
+    pub revealed: Vec<(usize, usize)>,
+    pub redacted: Vec<(usize, usize)>,
+}
+
+impl DisclosureCoverage {
+    /// Splits `0..total` into the given revealed ranges and the gaps between them
+    pub fn new(total: usize, ranges: &[(usize, usize)]) -> Self {
+        let mut ranges: Vec<(usize, usize)> = ranges
+            .iter()
+            .map(|&(start, end)| (start.min(total), end.min(total)))
+            .filter(|(start, end)| start < end)
+            .collect();
+        ranges.sort();
+
+        let mut revealed: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match revealed.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => revealed.push((start, end)),
+            }
+        }
+
+        let mut redacted = Vec::new();
+        let mut cursor = 0;
+        for &(start, end) in &revealed {
+            if cursor < start {
+                redacted.push((cursor, start));
+            }
+            cursor = end;
+        }
+        if cursor < total {
+            redacted.push((cursor, total));
+        }
+
+        DisclosureCoverage {
+            total,
+            revealed,
+            redacted,
+        }
+    }
+
+    pub fn revealed_bytes(&self) -> usize {
+        self.revealed.iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// Share of the transcript that is revealed, from 0 to 100
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.revealed_bytes() as f64 * 100.0 / self.total as f64
+    }
+}
+
+/// Byte ranges of a transcript index as `(start, end)` pairs
+pub fn index_ranges(index: &Idx) -> Vec<(usize, usize)> {
+    index
+        .iter_ranges()
+        .map(|range| (range.start, range.end))
+        .collect()
+}
+
+/// Received transcript as a verifier sees it: authenticated bytes in place and each
+/// redacted byte replaced by `marker`, so offsets line up with the reported ranges.
+/// Meant for auditors and dispute resolution, not for parsing.
+pub fn render_redacted_transcript(partial_transcript: &PartialTranscript, marker: char) -> String {
+    render_redacted(
+        partial_transcript.received_unsafe(),
+        &index_ranges(partial_transcript.received_authed()),
+        marker,
+    )
+}
+
+/// `data` with every byte outside `revealed` replaced by `marker`
+pub fn render_redacted(data: &[u8], revealed: &[(usize, usize)], marker: char) -> String {
+    let coverage = DisclosureCoverage::new(data.len(), revealed);
+    let mut segments: Vec<(usize, usize, bool)> = coverage
+        .revealed
+        .iter()
+        .map(|&(start, end)| (start, end, true))
+        .chain(
+            coverage
+                .redacted
+                .iter()
+                .map(|&(start, end)| (start, end, false)),
+        )
+        .collect();
+    segments.sort();
+
+    let mut rendered = String::with_capacity(data.len());
+    for (start, end, is_revealed) in segments {
+        if is_revealed {
+            rendered.push_str(&String::from_utf8_lossy(&data[start..end]));
+        } else {
+            rendered.extend(std::iter::repeat_n(marker, end - start));
+        }
+    }
+    rendered
+}
+
+/// Bytes of context shown on each side of a previewed commit range. Context stops at
+/// line breaks so a committed header never drags a neighboring `Cookie` into the logs.
+const PREVIEW_CONTEXT: usize = 16;
+/// Longest committed span shown in full; longer ones are elided in the middle
+const PREVIEW_MAX_LEN: usize = 64;
+
+/// A transcript range about to be committed, with a printable snippet of its bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitRange {
+    /// `sent` or `received`
+    pub direction: &'static str,
+    pub start: usize,
+    pub end: usize,
+    /// Escaped committed bytes in `[...]`, between a little surrounding context
+    pub snippet: String,
+}
+
+/// Shown the commit ranges before notarizing; returning `false` cancels notarization
+pub type ConfirmCommit = dyn Fn(&[CommitRange]) -> bool + Send + Sync;
+
+/// Describes each of `ranges` within one direction's transcript `data`
+pub fn commit_preview(
+    direction: &'static str,
+    data: &[u8],
+    ranges: &[(usize, usize)],
+) -> Vec<CommitRange> {
+    ranges
+        .iter()
+        .map(|&(start, end)| CommitRange {
+            direction,
+            start,
+            end,
+            snippet: commit_snippet(data, start, end),
+        })
+        .collect()
+}
+
+fn commit_snippet(data: &[u8], start: usize, end: usize) -> String {
+    let end = end.min(data.len());
+    let start = start.min(end);
+    let before = &data[start.saturating_sub(PREVIEW_CONTEXT)..start];
+    let before = match before
+        .iter()
+        .rposition(|&byte| byte == b'\n' || byte == b'\r')
+    {
+        Some(pos) => &before[pos + 1..],
+        None => before,
+    };
+    let after = &data[end..(end + PREVIEW_CONTEXT).min(data.len())];
+    let after = match after
+        .iter()
+        .position(|&byte| byte == b'\n' || byte == b'\r')
+    {
+        Some(pos) => &after[..pos],
+        None => after,
+    };
+    let committed = &data[start..end];
+
+    let committed = if committed.len() > PREVIEW_MAX_LEN {
+        let half = PREVIEW_MAX_LEN / 2;
+        format!(
+            "{}...{}",
+            printable(&committed[..half]),
+            printable(&committed[committed.len() - half..])
+        )
+    } else {
+        printable(committed)
+    };
+    format!("{}[{}]{}", printable(before), committed, printable(after))
+}
+
+/// Printable ASCII as is, everything else escaped
+fn printable(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            b' '..=b'~' => char::from(byte).to_string(),
+            _ => std::ascii::escape_default(byte).to_string(),
+        })
+        .collect()
+}
+
+pub fn print_commit_preview(ranges: &[CommitRange]) {
+    info!("{} ranges will be committed:", ranges.len());
+    for range in ranges {
+        info!(
+            "  {:<8} {:>6}..{:<6} {}",
+            range.direction, range.start, range.end, range.snippet
+        );
+    }
+}
+
+pub fn print_disclosure_coverage(direction: &str, coverage: &DisclosureCoverage) {
+    info!(
+        "{} disclosure: {}/{} bytes revealed ({:.1}%)",
+        direction,
+        coverage.revealed_bytes(),
+        coverage.total,
+        coverage.percentage()
+    );
+    info!("  Revealed: {:?}", coverage.revealed);
+    info!("  Redacted: {:?}", coverage.redacted);
+}
+
+/// Prints one row per transaction of a batch verification, then the totals
+pub fn print_batch_summary(
+    transaction_ids: &[String],
+    results: &[Result<VerificationReport, ZkpError>],
+) {
+    info!(
+        "{:<24} {:<8} {:>16}  {}",
+        "Transaction", "Status", "Amount", "Detail"
+    );
+    for (transaction_id, result) in transaction_ids.iter().zip(results) {
+        match result {
+            Ok(report) => {
+                let amount = match (&report.amount, &report.currency) {
+                    (Some(amount), Some(currency)) => format!("{} {}", amount, currency),
+                    (Some(amount), None) => amount.to_string(),
+                    _ => "-".to_string(),
+                };
+                info!(
+                    "{:<24} {:<8} {:>16}  {}",
+                    transaction_id, "OK", amount, report.server_name
+                );
+            }
+            Err(e) => info!("{:<24} {:<8} {:>16}  {}", transaction_id, "FAILED", "-", e),
+        }
+    }
+
+    let verified = results.iter().filter(|result| result.is_ok()).count();
+    info!("{} of {} presentations verified", verified, results.len());
+}
+
 pub fn print_verification_results(request_data: &[u8], response_data: &[u8], provider: &Provider) {
     let request = String::from_utf8_lossy(request_data);
     let response = String::from_utf8_lossy(response_data);
 
-    let field_ranges = find_field_ranges(&response_data, &provider);
+    let field_ranges = match find_field_ranges(response_data, provider) {
+        Ok(field_ranges) => field_ranges,
+        Err(e) => {
+            warn!("Failed to locate payment fields: {}", e);
+            Vec::new()
+        }
+    };
 
-    if field_ranges.len() > 0 {
+    if !field_ranges.is_empty() {
         info!(
             "Payment verification successful: {} fields verified",
             field_ranges.len()
@@ -51,3 +357,63 @@ pub fn print_verification_results(request_data: &[u8], response_data: &[u8], pro
     info!("ZKP2P verification completed - payment proof validated");
     info!("Proof ready for smart contract submission");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disclosure_coverage_merges_and_fills_gaps() {
+        let coverage = DisclosureCoverage::new(100, &[(40, 50), (10, 20), (15, 25), (90, 120)]);
+
+        assert_eq!(coverage.revealed, vec![(10, 25), (40, 50), (90, 100)]);
+        assert_eq!(coverage.redacted, vec![(0, 10), (25, 40), (50, 90)]);
+        assert_eq!(coverage.revealed_bytes(), 35);
+        assert!((coverage.percentage() - 35.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_plain_text_strips_symbols_and_colors() {
+        assert_eq!(
+            plain_text("     ✅ Found host header: range 16..33"),
+            "     Found host header: range 16..33"
+        );
+        assert_eq!(
+            plain_text("\x1b[32m INFO\x1b[0m 🔍 Verifying transaction presentation..."),
+            " INFO Verifying transaction presentation..."
+        );
+        assert_eq!(plain_text("Amount 12.50 €"), "Amount 12.50 ");
+    }
+
+    #[test]
+    fn test_commit_preview_shows_context() {
+        let data = b"GET /v1/transfers/42 HTTP/1.1\r\nhost: wise.com\r\ncookie: a=b\r\n\r\n";
+        let preview = commit_preview("sent", data, &[(31, 45)]);
+        assert_eq!(preview[0].direction, "sent");
+        assert_eq!(preview[0].snippet, "[host: wise.com]");
+
+        let body = br#"{"id":987654321,"state":"OUTGOING_PAYMENT_SENT","date":1}"#;
+        let preview = commit_preview("received", body, &[(16, 47)]);
+        assert_eq!(
+            preview[0].snippet,
+            r#"{"id":987654321,["state":"OUTGOING_PAYMENT_SENT"],"date":1}"#
+        );
+
+        let long = vec![b'a'; 100];
+        let snippet = &commit_preview("received", &long, &[(0, 100)])[0].snippet;
+        assert_eq!(
+            snippet,
+            &format!("[{}...{}]", "a".repeat(32), "a".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_render_redacted_keeps_offsets() {
+        let data = br#"{"id":123,"token":"secret","amount":9.5}"#;
+        let rendered = render_redacted(data, &[(1, 9), (27, 39)], '*');
+
+        assert_eq!(rendered, r#"*"id":123******************"amount":9.5*"#);
+        assert_eq!(rendered.len(), data.len());
+        assert_eq!(render_redacted(b"abc", &[], '#'), "###");
+    }
+}
@@ -1,15 +1,23 @@
 pub mod file_io;
+pub mod https;
 pub mod info;
+pub mod net;
 pub mod notary;
 pub mod patterns;
 pub mod providers;
+pub mod self_test;
+pub mod shutdown;
 pub mod text_parser;
 pub mod tls;
 
 pub use file_io::*;
+pub use https::*;
 pub use info::*;
+pub use net::*;
 pub use notary::*;
 pub use patterns::*;
 pub use providers::*;
+pub use self_test::*;
+pub use shutdown::*;
 pub use text_parser::*;
 pub use tls::*;
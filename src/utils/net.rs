@@ -0,0 +1,166 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+/// Time allowed for each resolved address before moving on to the next
+pub const CONNECT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default limit for connecting to the provider across all resolved addresses
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default limit for receiving the provider's response; MPC-TLS decryption is slow
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Resolves `host` and connects to the first address that answers.
+///
+/// With a `doh_url`, the host is resolved through that DNS-over-HTTPS JSON endpoint so
+/// the local resolver doesn't learn which provider is being proven; `None` uses system
+/// DNS. Addresses are tried happy-eyeballs style: the resolver's preferred family goes
+/// first, alternating with the other family, so a dead IPv6 route falls back to IPv4
+/// (and vice versa) instead of hanging.
+pub async fn connect_tcp(host: &str, port: u16, doh_url: Option<&str>) -> io::Result<TcpStream> {
+    let addrs = resolve(host, port, doh_url).await?;
+    connect_addrs(host, port, addrs).await
+}
+
+/// Resolves `host` and opens a throwaway TCP connection to it, which is dropped without
+/// sending anything. Nothing is notarized; the only effect is warmer DNS and OS caches,
+/// so an interactive app's first proof starts faster.
+pub async fn warmup(host: &str, port: u16, timeout: Duration) -> io::Result<()> {
+    let stream = tokio::time::timeout(timeout, connect_tcp(host, port, None))
+        .await
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("warming up {}:{} timed out", host, port),
+            )
+        })??;
+    drop(stream);
+    debug!("Warmed up connection to {}:{}", host, port);
+    Ok(())
+}
+
+/// Like `connect_tcp`, but always resolves through system DNS. Used to reach the DoH
+/// resolver itself.
+pub async fn connect_tcp_system(host: &str, port: u16) -> io::Result<TcpStream> {
+    let addrs = tokio::net::lookup_host((host, port)).await?.collect();
+    connect_addrs(host, port, addrs).await
+}
+
+/// Resolves through DoH when configured, falling back to system DNS if that fails
+async fn resolve(host: &str, port: u16, doh_url: Option<&str>) -> io::Result<Vec<SocketAddr>> {
+    // IP literals need no lookup at all
+    let doh_url = doh_url.filter(|_| host.parse::<IpAddr>().is_err());
+    if let Some(doh_url) = doh_url {
+        match crate::utils::https::doh_lookup(doh_url, host).await {
+            Ok(ips) if !ips.is_empty() => {
+                debug!("Resolved {} via DoH: {:?}", host, ips);
+                return Ok(ips
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, port))
+                    .collect());
+            }
+            Ok(_) => warn!(
+                "DoH returned no addresses for {}; falling back to system DNS",
+                host
+            ),
+            Err(e) => warn!(
+                "DoH lookup for {} failed: {:#}; falling back to system DNS",
+                host, e
+            ),
+        }
+    }
+
+    Ok(tokio::net::lookup_host((host, port)).await?.collect())
+}
+
+async fn connect_addrs(host: &str, port: u16, addrs: Vec<SocketAddr>) -> io::Result<TcpStream> {
+    let mut last_error = None;
+
+    for addr in interleave_families(addrs) {
+        match tokio::time::timeout(CONNECT_ATTEMPT_TIMEOUT, TcpStream::connect(addr)).await {
+            Ok(Ok(stream)) => {
+                info!("Connected to {}:{} via {}", host, port, addr);
+                return Ok(stream);
+            }
+            Ok(Err(e)) => {
+                debug!("Connection to {} failed: {}", addr, e);
+                last_error = Some(e);
+            }
+            Err(_) => {
+                debug!("Connection to {} timed out", addr);
+                last_error = Some(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("connection to {} timed out", addr),
+                ));
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no addresses resolved for {}", host),
+        )
+    }))
+}
+
+/// Orders addresses by alternating families, starting with the family of the first one
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(prefer_v6) = addrs.first().map(SocketAddr::is_ipv6) else {
+        return addrs;
+    };
+    let (preferred, fallback): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == prefer_v6);
+
+    let mut ordered = Vec::with_capacity(preferred.len() + fallback.len());
+    let mut preferred = preferred.into_iter();
+    let mut fallback = fallback.into_iter();
+    loop {
+        match (preferred.next(), fallback.next()) {
+            (None, None) => break,
+            (first, second) => ordered.extend(first.into_iter().chain(second)),
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_warmup_connects_and_drops() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        warmup("127.0.0.1", port, DEFAULT_CONNECT_TIMEOUT)
+            .await
+            .unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // The warmup connection carries no data
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut buf)
+            .await
+            .unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_interleave_families_alternates() {
+        let addrs: Vec<SocketAddr> = ["[::1]:443", "[::2]:443", "[::3]:443", "10.0.0.1:443"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+
+        let ordered: Vec<String> = interleave_families(addrs)
+            .iter()
+            .map(SocketAddr::to_string)
+            .collect();
+        assert_eq!(
+            ordered,
+            ["[::1]:443", "10.0.0.1:443", "[::2]:443", "[::3]:443"]
+        );
+    }
+}
@@ -1,5 +1,126 @@
 use notary_client::{Accepted, NotarizationRequest, NotaryClient};
-use tracing::debug;
+use serde::Deserialize;
+use tokio_rustls::rustls;
+use tracing::{debug, info, warn};
+
+use crate::domain::ServerConfig;
+use crate::error::ZkpError;
+use crate::utils::https::{plain_get, plain_http_get};
+
+/// Upper bound on the notary `/info` response
+const MAX_INFO_BODY_LEN: usize = 16 * 1024;
+
+/// Details a notary server reports about itself at `/info`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotaryInfo {
+    pub version: String,
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub git_commit_hash: Option<String>,
+}
+
+/// Checks the notary is reachable over the configured transport before requesting a session.
+///
+/// Returns the notary's `/info` when it exposes one, `None` when the endpoint is missing.
+/// A TLS handshake or HTTP parse failure usually means `tls_enabled` doesn't match the
+/// notary, which the returned error points out. Certificate failures only log a warning.
+/// `path_prefix` is expected without surrounding slashes, as `normalize_path_prefix` gives.
+pub async fn check_notary(
+    host: &str,
+    port: u16,
+    tls_enabled: bool,
+    path_prefix: Option<&str>,
+    user_agent: &str,
+) -> Result<Option<NotaryInfo>, ZkpError> {
+    let server = ServerConfig::new(host, port);
+    let scheme = if tls_enabled { "https" } else { "http" };
+    let url = match path_prefix {
+        Some(prefix) => format!("{}://{}:{}/{}/info", scheme, host, port, prefix),
+        None => format!("{}://{}:{}/info", scheme, host, port),
+    };
+    let description = "Notary pre-flight check (not notarized)";
+
+    let response = if tls_enabled {
+        plain_get(
+            &server,
+            &url,
+            &[],
+            description,
+            user_agent,
+            MAX_INFO_BODY_LEN,
+        )
+        .await
+    } else {
+        plain_http_get(&server, &url, description, user_agent, MAX_INFO_BODY_LEN).await
+    };
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) if is_certificate_error(&e) => {
+            // Self-signed notaries fail web PKI checks but the notary client may trust them
+            warn!("Skipping notary pre-flight: {:#}", e);
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(ZkpError::NotaryConnection {
+                host: host.to_string(),
+                port,
+                reason: format!("{:#}{}", e, connection_hint(&e, tls_enabled)),
+            });
+        }
+    };
+
+    if !response.status.is_success() {
+        debug!(
+            "Notary returned {} for /info; skipping info",
+            response.status
+        );
+        return Ok(None);
+    }
+
+    match serde_json::from_slice::<NotaryInfo>(&response.body) {
+        Ok(notary_info) => {
+            info!(
+                "Notary {}:{} is up (version {})",
+                host, port, notary_info.version
+            );
+            Ok(Some(notary_info))
+        }
+        Err(e) => {
+            debug!("Unrecognized notary /info response: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+fn is_certificate_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io_error| io_error.get_ref())
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+            .is_some_and(|tls_error| matches!(tls_error, rustls::Error::InvalidCertificate(_)))
+    })
+}
+
+fn connection_hint(error: &anyhow::Error, tls_enabled: bool) -> &'static str {
+    let handshake_failed = error
+        .chain()
+        .any(|cause| cause.to_string().contains("TLS handshake failed"));
+    let parse_failed = error.chain().any(|cause| {
+        cause
+            .downcast_ref::<hyper::Error>()
+            .is_some_and(hyper::Error::is_parse)
+    });
+
+    match (tls_enabled, handshake_failed, parse_failed) {
+        (true, true, _) => " (the notary may not serve TLS; try tls_enabled = false)",
+        (false, _, true) => " (the notary may require TLS; try tls_enabled = true)",
+        _ => "",
+    }
+}
 
 /// Requests notarization from the notary server
 pub async fn request_notarization(
@@ -1,24 +1,150 @@
-use crate::domain::Provider;
-
-const EMPTY_PATTERNS: &[(&str, &str)] = &[];
+use crate::domain::{Provider, ZelleBank};
 
 pub const WISE_FIELD_PATTERNS: &[(&str, &str)] = &[
     (r#""id":([0-9]+)"#, "paymentId"),
     (r#""state":"([^"]+)""#, "state"),
-    (
-        r#""state":"OUTGOING_PAYMENT_SENT","date":([0-9]+)"#,
-        "timestamp",
-    ),
+    (r#""state":"[A-Z_]+","date":([0-9]+)"#, "timestamp"),
     (r#""targetAmount":([0-9\.]+)"#, "targetAmount"),
     (r#""targetCurrency":"([^"]+)""#, "targetCurrency"),
     (r#""targetRecipientId":([0-9]+)"#, "targetRecipientId"),
+    // Conversion details; absent on same-currency transfers, so never required
+    (r#""sourceAmount":([0-9\.]+)"#, "sourceAmount"),
+    (r#""sourceCurrency":"([^"]+)""#, "sourceCurrency"),
+    (r#""fee":([0-9\.]+)"#, "fee"),
+    (r#""rate":([0-9\.]+)"#, "rate"),
+];
+
+/// Revolut's transaction endpoint returns an array of legs; `amount` is a signed
+/// integer in minor units, negative for outgoing payments
+pub const REVOLUT_FIELD_PATTERNS: &[(&str, &str)] = &[
+    (r#""id":"([0-9a-f-]+)""#, "transactionId"),
+    (r#""state":"([A-Z_]+)""#, "state"),
+    (r#""completedDate":([0-9]+)"#, "completedDate"),
+    (r#""amount":(-?[0-9]+)"#, "amount"),
+    (r#""currency":"([A-Z]{3})""#, "currency"),
+];
+
+/// Mercado Pago's payment resource; amounts are major-unit decimals, statuses are
+/// lowercase (`approved`) and `date_approved` is an ISO 8601 timestamp with offset
+pub const MERCADOPAGO_FIELD_PATTERNS: &[(&str, &str)] = &[
+    (r#""id":([0-9]+)"#, "paymentId"),
+    (r#""status":"([a-z_]+)""#, "status"),
+    (r#""date_approved":"([0-9T:.+-]+)""#, "date_approved"),
+    (r#""currency_id":"([A-Z]{3})""#, "currency_id"),
+    (r#""transaction_amount":([0-9\.]+)"#, "transaction_amount"),
+];
+
+/// Cash App's activity entry; `amount` sits in a money object in minor units (cents),
+/// the payment is identified by its `token` and `created_at` is in epoch milliseconds
+pub const CASHAPP_FIELD_PATTERNS: &[(&str, &str)] = &[
+    (r#""token":"([A-Za-z0-9_]+)""#, "paymentToken"),
+    (r#""status":"([A-Z_]+)""#, "status"),
+    (r#""amount":([0-9]+)"#, "amount"),
+    (r#""currency_code":"([A-Z]{3})""#, "currency"),
+    (r#""created_at":([0-9]+)"#, "createdAt"),
+];
+
+pub const PAYPAL_FIELD_PATTERNS: &[(&str, &str)] = &[
+    (r#""transactionId":"([A-Z0-9]+)""#, "transactionId"),
+    (r#""status":"([A-Z_]+)""#, "status"),
 ];
 
+/// PayPal wraps money as `{"value":"10.00","currency_code":"USD"}` and a response holds
+/// several such objects (amount, fee, net); entries are (object key, pattern, field name)
+/// with the pattern searched only inside the keyed object
+pub const PAYPAL_NESTED_FIELD_PATTERNS: &[(&str, &str, &str)] = &[
+    ("amount", r#""value":"([0-9.]+)""#, "amount"),
+    ("amount", r#""currency_code":"([A-Z]{3})""#, "currency"),
+];
+
+/// Chase's Zelle activity detail; amounts are USD decimals and `sendDate` is `YYYYMMDD`
+pub const CHASE_ZELLE_FIELD_PATTERNS: &[(&str, &str)] = &[
+    (r#""paymentId":"([0-9]+)""#, "paymentId"),
+    (r#""status":"([A-Z_]+)""#, "status"),
+    (r#""amount":([0-9\.]+)"#, "amount"),
+    (r#""recipientToken":"([^"]+)""#, "recipientToken"),
+    (r#""sendDate":"([0-9]{8})""#, "sendDate"),
+];
+
+/// Names of the (amount, currency) fields used to normalize the payment value
+pub const WISE_PAYMENT_FIELDS: (&str, &str) = ("targetAmount", "targetCurrency");
+pub const PAYPAL_PAYMENT_FIELDS: (&str, &str) = ("amount", "currency");
+pub const MERCADOPAGO_PAYMENT_FIELDS: (&str, &str) = ("transaction_amount", "currency_id");
+
+/// Names of the optional (fee, exchange rate) fields reported when revealed
+pub const WISE_CONVERSION_FIELDS: (&str, &str) = ("fee", "rate");
+
+/// Wise fields committed but only revealed when asked for by name; what the sender
+/// paid and the conversion applied aren't needed to settle the received amount
+pub const WISE_OPT_IN_FIELDS: &[&str] = &["sourceAmount", "sourceCurrency", "fee", "rate"];
+
+/// Name of the field holding the server's echo of a caller-supplied nonce
+pub const NONCE_FIELD: &str = "nonce";
+
 pub const HOST_HEADER_PATTERN: &str = r"host: [^\r\n]+";
 
 pub fn get_field_patterns(provider: &Provider) -> &'static [(&'static str, &'static str)] {
     match provider {
         Provider::Wise => WISE_FIELD_PATTERNS,
-        Provider::PayPal => EMPTY_PATTERNS,
+        Provider::PayPal => PAYPAL_FIELD_PATTERNS,
+        Provider::Revolut => REVOLUT_FIELD_PATTERNS,
+        Provider::MercadoPago => MERCADOPAGO_FIELD_PATTERNS,
+        Provider::CashApp => CASHAPP_FIELD_PATTERNS,
+        Provider::Zelle(bank) => get_zelle_field_patterns(bank),
+    }
+}
+
+/// Second level of the lookup: Zelle responses are shaped by the bank serving them
+pub fn get_zelle_field_patterns(bank: &ZelleBank) -> &'static [(&'static str, &'static str)] {
+    match bank {
+        ZelleBank::Chase => CHASE_ZELLE_FIELD_PATTERNS,
+    }
+}
+
+pub fn get_nested_field_patterns(
+    provider: &Provider,
+) -> &'static [(&'static str, &'static str, &'static str)] {
+    match provider {
+        Provider::PayPal => PAYPAL_NESTED_FIELD_PATTERNS,
+        Provider::Wise
+        | Provider::Revolut
+        | Provider::MercadoPago
+        | Provider::CashApp
+        | Provider::Zelle(_) => &[],
+    }
+}
+
+pub fn get_payment_fields(provider: &Provider) -> Option<(&'static str, &'static str)> {
+    match provider {
+        Provider::Wise => Some(WISE_PAYMENT_FIELDS),
+        Provider::PayPal => Some(PAYPAL_PAYMENT_FIELDS),
+        Provider::MercadoPago => Some(MERCADOPAGO_PAYMENT_FIELDS),
+        // Minor-unit amounts would be misread as a major-unit decimal
+        Provider::Revolut | Provider::CashApp => None,
+        // Zelle is USD-only, so responses carry no currency field to reveal
+        Provider::Zelle(_) => None,
+    }
+}
+
+/// Fields left out of a presentation unless the caller names them
+pub fn get_opt_in_fields(provider: &Provider) -> &'static [&'static str] {
+    match provider {
+        Provider::Wise => WISE_OPT_IN_FIELDS,
+        Provider::PayPal
+        | Provider::Revolut
+        | Provider::MercadoPago
+        | Provider::CashApp
+        | Provider::Zelle(_) => &[],
+    }
+}
+
+pub fn get_conversion_fields(provider: &Provider) -> Option<(&'static str, &'static str)> {
+    match provider {
+        Provider::Wise => Some(WISE_CONVERSION_FIELDS),
+        Provider::PayPal
+        | Provider::Revolut
+        | Provider::MercadoPago
+        | Provider::CashApp
+        | Provider::Zelle(_) => None,
     }
 }
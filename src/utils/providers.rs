@@ -1,43 +1,525 @@
-use crate::domain::{ProviderConfig, ServerConfig};
-use crate::utils::tls::build_request;
+use crate::domain::{Provider, ProviderConfig, ProviderInfo, RequestStep, ServerConfig};
+use crate::error::ZkpError;
+use crate::utils::https::{plain_get, read_body_limited, retry_after};
+use crate::utils::patterns::{get_field_patterns, get_nested_field_patterns};
+use crate::utils::text_parser::analyze_transcript;
+use crate::utils::tls::{ConnectionMode, RequestSender, build_request, serialized_request_len};
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use hyper::StatusCode;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
 
-pub async fn execute_transaction_request(
-    request_sender: &mut hyper::client::conn::http1::SendRequest<
-        http_body_util::Empty<hyper::body::Bytes>,
-    >,
+/// Lists every provider this build supports along with the fields it reveals
+pub fn supported_providers() -> Vec<ProviderInfo> {
+    Provider::value_variants()
+        .iter()
+        .map(|provider| ProviderInfo {
+            name: provider.to_string(),
+            endpoint_template: provider.endpoint_template().to_string(),
+            fields: get_field_patterns(provider)
+                .iter()
+                .map(|(_, field_name)| field_name.to_string())
+                .chain(
+                    get_nested_field_patterns(provider)
+                        .iter()
+                        .map(|(_, _, field_name)| field_name.to_string()),
+                )
+                .collect(),
+        })
+        .collect()
+}
+
+/// Supplies a fresh access token when the provider rejects the current one
+pub type TokenRefresh = dyn Fn() -> Option<String> + Send + Sync;
+
+/// Runs the provider's request steps over the notarized connection.
+///
+/// Each step's response must have the step's required status before the next step is
+/// sent; intermediate bodies are drained so the connection can carry the next request.
+/// With a `token_refresh` callback the connection is kept alive after the final step's
+/// first attempt, and a 401 triggers exactly one retry with the refreshed access token.
+pub async fn execute_request_steps(
+    request_sender: &mut RequestSender,
     url: &str,
     provider: &ProviderConfig,
     server: &ServerConfig,
     user_agent: &str,
+    max_recv_data: usize,
+    token_refresh: Option<&TokenRefresh>,
 ) -> Result<()> {
-    let headers = provider.auth_headers();
-    let request = build_request(
-        &url,
-        &server.host,
+    let steps = provider.provider_type.request_steps();
+    let mut provider = provider.clone();
+    let mut token_refresh = token_refresh;
+
+    for (index, step) in steps.iter().enumerate() {
+        let is_final = index + 1 == steps.len();
+        let step_url = step
+            .resolve(&provider.provider_type, url)
+            .map_err(anyhow::Error::msg)?;
+        let response = send_transaction_request(
+            request_sender,
+            step,
+            &step_url,
+            &provider,
+            server,
+            user_agent,
+            ConnectionMode::for_request(is_final && token_refresh.is_none()),
+        )
+        .await?;
+
+        let response = match token_refresh.take() {
+            Some(refresh) if response.status() == StatusCode::UNAUTHORIZED => {
+                // Drain the rejected body so the connection can carry the retry
+                read_body_limited(response.into_body(), max_recv_data).await?;
+                provider.access_token = refresh()
+                    .ok_or_else(|| anyhow::anyhow!("Access token rejected and refresh failed"))?;
+                info!("Access token rejected, retrying once with a refreshed token");
+
+                send_transaction_request(
+                    request_sender,
+                    step,
+                    &step_url,
+                    &provider,
+                    server,
+                    user_agent,
+                    ConnectionMode::for_request(is_final),
+                )
+                .await?
+            }
+            refresh => {
+                token_refresh = refresh;
+                response
+            }
+        };
+
+        let status = response.status();
+        // The notarized session can't wait, so the caller decides when to try again
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(ZkpError::RateLimited {
+                retry_after: retry_after(response.headers()).map(|wait| wait.as_secs()),
+            }
+            .into());
+        }
+        if status.as_u16() != step.required_status {
+            let body = read_body_limited(response.into_body(), max_recv_data)
+                .await
+                .unwrap_or_default();
+
+            let hint = status_hint(status)
+                .map(|hint| format!(" ({hint})"))
+                .unwrap_or_default();
+
+            return Err(anyhow::anyhow!(
+                "❌ Request step {} of {} failed - Server returned: {}{}, expected {}\nResponse body: {}",
+                index + 1,
+                steps.len(),
+                status,
+                hint,
+                step.required_status,
+                truncate_body(&body)
+            ));
+        }
+
+        // Identity is requested but not every server honors it
+        if let Some(encoding) = response.headers().get(hyper::header::CONTENT_ENCODING) {
+            let encoding = encoding.to_str().unwrap_or_default().trim();
+            if !encoding.is_empty() && !encoding.eq_ignore_ascii_case("identity") {
+                return Err(ZkpError::CompressedResponse(encoding.to_string()).into());
+            }
+        }
+
+        // The final body is read too, so a response past max_recv_data fails here
+        // rather than as a truncated transcript
+        read_body_limited(response.into_body(), max_recv_data).await?;
+        debug!("Request step {} of {} completed", index + 1, steps.len());
+    }
+
+    Ok(())
+}
+
+/// Total size of the requests `execute_request_steps` sends, checked against
+/// `max_sent_data` before a notary session is spent on requests that can't fit.
+///
+/// A token-refresh retry isn't counted, since the refreshed token's length is unknown.
+pub fn check_request_size(
+    url: &str,
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+    max_sent_data: usize,
+) -> Result<()> {
+    let steps = provider.provider_type.request_steps();
+    let mut size = 0;
+    for (index, step) in steps.iter().enumerate() {
+        let step_url = step
+            .resolve(&provider.provider_type, url)
+            .map_err(anyhow::Error::msg)?;
+        // Keep-alive is the longer header value, so the estimate never falls short
+        let request = transaction_request(
+            step,
+            &step_url,
+            provider,
+            server,
+            user_agent,
+            ConnectionMode::KeepAlive,
+        )?;
+        let len = serialized_request_len(&request);
+        debug!("Request step {} takes {} bytes", index + 1, len);
+        size += len;
+    }
+
+    if size > max_sent_data {
+        return Err(ZkpError::RequestTooLarge {
+            size,
+            limit: max_sent_data,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+async fn send_transaction_request(
+    request_sender: &mut RequestSender,
+    step: &RequestStep,
+    url: &str,
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+    connection: ConnectionMode,
+) -> Result<hyper::Response<hyper::body::Incoming>> {
+    let request = transaction_request(step, url, provider, server, user_agent, connection)?;
+
+    request_sender
+        .send_request(request)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to send request: {e}"))
+}
+
+fn transaction_request(
+    step: &RequestStep,
+    url: &str,
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+    connection: ConnectionMode,
+) -> Result<hyper::Request<http_body_util::Empty<hyper::body::Bytes>>> {
+    let request_headers = provider.request_headers();
+    let headers: Vec<(&str, &str)> = request_headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let mut request = build_request(
+        url,
+        server,
         &headers,
         "Requesting specific transaction details for attestation",
         user_agent,
+        connection,
+        server.http_version,
     )
     .context("Failed to build request")?;
+    *request.method_mut() = step.method.clone();
+    Ok(request)
+}
 
-    request_sender
-        .send_request(request)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to send request: {e}"))
-        .and_then(|response| {
-            response
-                .status()
-                .eq(&StatusCode::OK)
-                .then_some(())
-                .ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "❌ Transaction request failed - Server returned: {}",
-                        response.status()
-                    )
-                })
-        })?;
+/// Upper bound on bodies read by non-notarized pre-flight requests
+const MAX_PLAIN_BODY_LEN: usize = 1 << 20;
 
-    Ok(())
+/// Looks up the account's profile id with a plain, non-notarized HTTPS request.
+///
+/// Only Wise exposes profile discovery; the personal profile is preferred when the
+/// account has several.
+pub async fn fetch_profile_id(
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+) -> Result<String> {
+    if provider.provider_type != Provider::Wise {
+        anyhow::bail!(
+            "Profile discovery is not supported for {}",
+            provider.provider_type
+        );
+    }
+
+    let request_headers = provider.request_headers();
+    let headers: Vec<(&str, &str)> = request_headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let url = format!("https://{}/gateway/v1/profiles", server.host);
+    let response = plain_get(
+        server,
+        &url,
+        &headers,
+        "Discovering profile id (not notarized)",
+        user_agent,
+        MAX_PLAIN_BODY_LEN,
+    )
+    .await?;
+
+    if response.status != StatusCode::OK {
+        let hint = status_hint(response.status)
+            .map(|hint| format!(" ({hint})"))
+            .unwrap_or_default();
+        anyhow::bail!(
+            "Profile lookup failed - Server returned: {}{}",
+            response.status,
+            hint
+        );
+    }
+
+    let profiles: Vec<serde_json::Value> =
+        serde_json::from_slice(&response.body).context("Unexpected profiles response")?;
+    let profile = profiles
+        .iter()
+        .find(|profile| profile["type"].as_str() == Some("personal"))
+        .or_else(|| profiles.first())
+        .ok_or_else(|| anyhow::anyhow!("No profiles found for this account"))?;
+
+    match &profile["id"] {
+        serde_json::Value::Number(id) => Ok(id.to_string()),
+        serde_json::Value::String(id) => Ok(id.clone()),
+        _ => anyhow::bail!("Profile entry has no id"),
+    }
+}
+
+/// Finds which of the candidate profiles holds the transaction, trying each one's
+/// details endpoint with a plain, non-notarized request until one returns 200.
+///
+/// Rejected credentials end the search early, since no other profile would accept them.
+pub async fn find_profile_id(
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+    candidates: &[String],
+    transaction_id: &str,
+) -> Result<String> {
+    let request_headers = provider.request_headers();
+    let headers: Vec<(&str, &str)> = request_headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+
+    for profile_id in candidates {
+        let url = provider
+            .provider_type
+            .transaction_endpoint(Some(profile_id), transaction_id)
+            .map_err(anyhow::Error::msg)?;
+        let response = plain_get(
+            server,
+            &url,
+            &headers,
+            "Looking up transaction profile (not notarized)",
+            user_agent,
+            MAX_PLAIN_BODY_LEN,
+        )
+        .await?;
+
+        match response.status {
+            StatusCode::OK => {
+                info!(
+                    "Transaction {} belongs to profile {}",
+                    transaction_id, profile_id
+                );
+                return Ok(profile_id.clone());
+            }
+            StatusCode::UNAUTHORIZED => anyhow::bail!(
+                "Profile lookup failed - Server returned: {} ({})",
+                response.status,
+                status_hint(response.status).unwrap_or_default()
+            ),
+            status => debug!("Profile {} returned {}", profile_id, status),
+        }
+    }
+
+    Err(ZkpError::NoMatchingProfile {
+        transaction_id: transaction_id.to_string(),
+        candidates: candidates.to_vec(),
+    }
+    .into())
+}
+
+/// Polls the transaction's details endpoint with plain, non-notarized requests until its
+/// status is one of `server.settled_states`, so a proof isn't wasted on a pending payment.
+///
+/// Fails with `ZkpError::Timeout` when the status hasn't settled within `timeout`.
+pub async fn wait_for_settlement(
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+    url: &str,
+    timeout: Duration,
+) -> Result<()> {
+    if server.settled_states.is_empty() {
+        return Err(ZkpError::Config(format!(
+            "No settled_states are configured for {}",
+            provider.provider_type
+        ))
+        .into());
+    }
+    let request_headers = provider.request_headers();
+    let headers: Vec<(&str, &str)> = request_headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let interval = Duration::from_secs(server.settle_poll_interval);
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let response = plain_get(
+            server,
+            url,
+            &headers,
+            "Polling transaction status (not notarized)",
+            user_agent,
+            MAX_PLAIN_BODY_LEN,
+        )
+        .await?;
+        if response.status != StatusCode::OK {
+            let hint = status_hint(response.status)
+                .map(|hint| format!(" ({hint})"))
+                .unwrap_or_default();
+            anyhow::bail!(
+                "Status poll failed - Server returned: {}{}",
+                response.status,
+                hint
+            );
+        }
+
+        let status = transaction_status(&response.body, &provider.provider_type);
+        if let Some(settled) = status.as_deref().filter(|status| {
+            server
+                .settled_states
+                .iter()
+                .any(|settled| settled == status)
+        }) {
+            info!("Transaction reached {}", settled);
+            return Ok(());
+        }
+
+        if Instant::now() + interval > deadline {
+            return Err(ZkpError::Timeout {
+                stage: "settlement",
+                secs: timeout.as_secs(),
+            }
+            .into());
+        }
+        info!(
+            "Transaction is {}, waiting {}s for {}",
+            status.as_deref().unwrap_or("in an unknown state"),
+            interval.as_secs(),
+            server.settled_states.join(" or ")
+        );
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Status field of a details response body, as the provider's metadata extractor reads it
+fn transaction_status(body: &[u8], provider: &Provider) -> Option<String> {
+    analyze_transcript(body, provider).map(|metadata| metadata.status)
+}
+
+/// Checks credentials with a plain, non-notarized request to a lightweight endpoint.
+///
+/// Returns `Ok(false)` when the provider rejects them, either with 401/403 or by
+/// redirecting to its login page.
+pub async fn check_credentials(
+    provider: &ProviderConfig,
+    server: &ServerConfig,
+    user_agent: &str,
+) -> Result<bool, ZkpError> {
+    provider.validate_credentials()?;
+    let request_headers = provider.request_headers();
+    let headers: Vec<(&str, &str)> = request_headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let url = format!(
+        "https://{}{}",
+        server.host,
+        provider.provider_type.credential_check_path()
+    );
+    let response = plain_get(
+        server,
+        &url,
+        &headers,
+        "Checking credentials (not notarized)",
+        user_agent,
+        MAX_PLAIN_BODY_LEN,
+    )
+    .await
+    .map_err(|e| ZkpError::Http(e.to_string()))?;
+
+    match response.status {
+        status if status.is_success() => Ok(true),
+        status if status.is_redirection() => Ok(false),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Ok(false),
+        status => Err(ZkpError::Http(format!(
+            "Credential check returned {}",
+            status
+        ))),
+    }
+}
+
+/// Maximum number of characters of an error response body to include in errors
+const MAX_ERROR_BODY_CHARS: usize = 512;
+
+fn status_hint(status: StatusCode) -> Option<&'static str> {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            Some("credentials expired - refresh your cookie and access token")
+        }
+        StatusCode::NOT_FOUND => Some("transaction not found"),
+        StatusCode::TOO_MANY_REQUESTS => Some("rate limited - try again later"),
+        _ => None,
+    }
+}
+
+fn truncate_body(body: &[u8]) -> String {
+    let body = String::from_utf8_lossy(body);
+    if body.chars().count() <= MAX_ERROR_BODY_CHARS {
+        return body.into_owned();
+    }
+
+    let truncated: String = body.chars().take(MAX_ERROR_BODY_CHARS).collect();
+    format!("{truncated}... (truncated)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_status_reads_provider_state() {
+        let body = br#"{"id":987654321,"state":"PROCESSING","targetAmount":25.0,"targetCurrency":"EUR","targetRecipientId":42}"#;
+        assert_eq!(
+            transaction_status(body, &Provider::Wise).as_deref(),
+            Some("PROCESSING")
+        );
+        assert_eq!(transaction_status(b"{}", &Provider::Wise), None);
+    }
+
+    #[test]
+    fn test_request_size_checked_against_max_sent_data() {
+        let url = "https://wise.com/gateway/v3/profiles/1/transfers/2";
+        let server = ServerConfig::new("wise.com", 443);
+        let provider = ProviderConfig::new(
+            Provider::Wise,
+            "session=abc".to_string(),
+            "token".to_string(),
+        );
+        assert!(check_request_size(url, &provider, &server, "agent", 4096).is_ok());
+
+        let provider = ProviderConfig::new(
+            Provider::Wise,
+            format!("session={}", "a".repeat(5000)),
+            "token".to_string(),
+        );
+        let error = check_request_size(url, &provider, &server, "agent", 4096).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ZkpError>(),
+            Some(ZkpError::RequestTooLarge { limit: 4096, .. })
+        ));
+    }
 }
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result, anyhow};
+use hyper::StatusCode;
+use hyper_util::rt::TokioIo;
+use tls_core::{anchors::RootCertStore, key::Certificate, verify::WebPkiVerifier};
+use tlsn_common::config::{ProtocolConfig, ProtocolConfigValidator};
+use tlsn_core::{
+    CryptoProvider, attestation::AttestationConfig, presentation::PresentationOutput,
+    request::RequestConfig, transcript::TranscriptCommitConfig,
+};
+use tlsn_prover::{Prover, ProverConfig};
+use tlsn_server_fixture_certs::{CA_CERT_DER, SERVER_DOMAIN};
+use tlsn_verifier::{Verifier, VerifierConfig};
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+use tracing::{debug, info};
+
+use crate::domain::{HttpVersion, ServerConfig};
+use crate::utils::shutdown::AbortOnDrop;
+use crate::utils::text_parser::find_host_header_range;
+use crate::utils::tls::{ConnectionMode, build_request};
+
+/// Fixed signing key for the in-process notary; self-test attestations are throwaway
+const SELF_TEST_NOTARY_KEY: [u8; 32] = [1u8; 32];
+const SELF_TEST_PATH: &str = "/formats/json";
+const DUPLEX_BUFFER: usize = 1 << 16;
+
+/// Runs a full prove → present → verify cycle against the TLSNotary fixture server.
+///
+/// The notary and fixture server both run in-process over in-memory sockets, so no
+/// provider credentials or network access are needed. Succeeds only if the revealed
+/// Host header verifies against the fixture's certificate.
+pub async fn run_self_test(max_sent_data: usize, max_recv_data: usize) -> Result<()> {
+    let (prover_socket, notary_socket) = tokio::io::duplex(DUPLEX_BUFFER);
+    let notary = tokio::spawn(run_notary(notary_socket, max_sent_data, max_recv_data));
+    debug!("In-process notary started");
+
+    let (client_socket, server_socket) = tokio::io::duplex(DUPLEX_BUFFER);
+    let server = tokio::spawn(tlsn_server_fixture::bind(server_socket.compat()));
+    debug!("Fixture server started for {}", SERVER_DOMAIN);
+
+    let prover_config = ProverConfig::builder()
+        .server_name(SERVER_DOMAIN)
+        .protocol_config(
+            ProtocolConfig::builder()
+                .max_sent_data(max_sent_data)
+                .max_recv_data(max_recv_data)
+                .build()?,
+        )
+        .crypto_provider(fixture_crypto_provider()?)
+        .build()?;
+
+    let prover = Prover::new(prover_config)
+        .setup(prover_socket.compat())
+        .await?;
+    let (mpc_tls_connection, prover_fut) = prover.connect(client_socket.compat()).await?;
+    let prover_task = AbortOnDrop::spawn(prover_fut);
+    let (mut request_sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(mpc_tls_connection.compat())).await?;
+    tokio::spawn(connection);
+    debug!("MPC-TLS connection to fixture established");
+
+    let request = build_request(
+        &format!("https://{}{}", SERVER_DOMAIN, SELF_TEST_PATH),
+        &ServerConfig::new(SERVER_DOMAIN, 443),
+        &[],
+        "Self-test request to fixture server",
+        "tlsnprover-self-test",
+        ConnectionMode::Close,
+        HttpVersion::Http11,
+    )?;
+    let response = request_sender.send_request(request).await?;
+    if response.status() != StatusCode::OK {
+        return Err(anyhow!("Fixture server returned {}", response.status()));
+    }
+    drop(request_sender);
+
+    let mut prover = prover_task.await??;
+    let header_range = find_host_header_range(prover.transcript().sent())
+        .ok_or_else(|| anyhow!("Host header not found in sent transcript"))?;
+    let received_len = prover.transcript().received().len();
+
+    let mut builder = TranscriptCommitConfig::builder(prover.transcript());
+    builder.commit_sent(&(header_range.0..header_range.1))?;
+    builder.commit_recv(&(0..received_len))?;
+    let mut request_config = RequestConfig::builder();
+    request_config.transcript_commit(builder.build()?);
+
+    #[allow(deprecated)]
+    let (attestation, secrets) = prover.notarize(&request_config.build()?).await?;
+    info!("Self-test notarization completed");
+
+    let mut builder = secrets.transcript_proof_builder();
+    builder.reveal_sent(&(header_range.0..header_range.1))?;
+    builder.reveal_recv(&(0..received_len))?;
+    let transcript_proof = builder.build()?;
+
+    let crypto_provider = fixture_crypto_provider()?;
+    let mut builder = attestation.presentation_builder(&crypto_provider);
+    builder
+        .identity_proof(secrets.identity_proof())
+        .transcript_proof(transcript_proof);
+    let presentation = builder.build()?;
+    debug!("Self-test presentation built");
+
+    let PresentationOutput {
+        server_name,
+        transcript,
+        ..
+    } = presentation
+        .verify(&crypto_provider)
+        .context("Self-test presentation failed verification")?;
+
+    let server_name = server_name.ok_or_else(|| anyhow!("Server name was not revealed"))?;
+    if server_name.as_str() != SERVER_DOMAIN {
+        return Err(anyhow!(
+            "Verified server name {} is not {}",
+            server_name,
+            SERVER_DOMAIN
+        ));
+    }
+
+    let transcript = transcript.ok_or_else(|| anyhow!("Transcript was not revealed"))?;
+    let revealed_host = &transcript.sent_unsafe()[header_range.0..header_range.1];
+    if !String::from_utf8_lossy(revealed_host).contains(SERVER_DOMAIN) {
+        return Err(anyhow!(
+            "Revealed Host header does not match {}",
+            SERVER_DOMAIN
+        ));
+    }
+
+    notary.await??;
+    server.abort();
+    info!(
+        "✅ Self-test passed: Host header revealed and verified for {}",
+        server_name
+    );
+
+    Ok(())
+}
+
+fn fixture_crypto_provider() -> Result<CryptoProvider> {
+    let mut root_store = RootCertStore::empty();
+    root_store
+        .add(&Certificate(CA_CERT_DER.to_vec()))
+        .map_err(|e| anyhow!("Invalid fixture CA certificate: {:?}", e))?;
+
+    Ok(CryptoProvider {
+        cert: WebPkiVerifier::new(root_store, None),
+        ..Default::default()
+    })
+}
+
+async fn run_notary(
+    socket: tokio::io::DuplexStream,
+    max_sent_data: usize,
+    max_recv_data: usize,
+) -> Result<()> {
+    let mut crypto_provider = CryptoProvider::default();
+    crypto_provider
+        .signer
+        .set_secp256k1(&SELF_TEST_NOTARY_KEY)
+        .map_err(|e| anyhow!("Failed to load self-test notary key: {}", e))?;
+
+    let mut attestation_config = AttestationConfig::builder();
+    attestation_config
+        .supported_signature_algs(crypto_provider.signer.supported_algs().collect::<Vec<_>>());
+    let attestation_config = attestation_config.build()?;
+
+    let verifier_config = VerifierConfig::builder()
+        .protocol_config_validator(
+            ProtocolConfigValidator::builder()
+                .max_sent_data(max_sent_data)
+                .max_recv_data(max_recv_data)
+                .build()?,
+        )
+        .crypto_provider(crypto_provider)
+        .build()?;
+
+    #[allow(deprecated)]
+    Verifier::new(verifier_config)
+        .notarize(socket.compat(), &attestation_config)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::{JoinError, JoinHandle};
+use tracing::{info, warn};
+
+use crate::utils::file_io::remove_partial_artifacts;
+
+/// Runs `task` until it finishes or the user presses Ctrl-C.
+///
+/// On interrupt the task is dropped, which cancels its in-flight requests, and any
+/// artifact whose write was cut short is deleted so no truncated `.tlsn` file remains.
+pub async fn run_until_interrupted<T>(
+    task: impl Future<Output = Result<T, Box<dyn std::error::Error>>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    tokio::pin!(task);
+    tokio::select! {
+        result = &mut task => result,
+        signal = tokio::signal::ctrl_c() => match signal {
+            Ok(()) => {
+                let removed = remove_partial_artifacts();
+                info!("Interrupted; removed {} partial artifact(s), shutting down", removed);
+                Err("Interrupted by Ctrl-C".into())
+            }
+            Err(e) => {
+                warn!("Failed to listen for Ctrl-C: {}", e);
+                task.await
+            }
+        },
+    }
+}
+
+/// A spawned task that is aborted when its handle is dropped.
+///
+/// Plain `tokio::spawn` detaches the task, so an error path that returns before
+/// awaiting it would leave it running; in the long-lived FFI runtime those leak.
+pub struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T: Send + 'static> AbortOnDrop<T> {
+    pub fn spawn(task: impl Future<Output = T> + Send + 'static) -> Self {
+        AbortOnDrop(tokio::spawn(task))
+    }
+}
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dropped_task_is_aborted() {
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        let task = AbortOnDrop::spawn(async move {
+            let _sender = sender;
+            std::future::pending::<()>().await
+        });
+
+        drop(task);
+        // The aborted task drops its sender, closing the channel
+        assert!(receiver.await.is_err());
+
+        assert_eq!(AbortOnDrop::spawn(async { 7 }).await.unwrap(), 7);
+    }
+}
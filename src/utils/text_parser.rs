@@ -1,71 +1,1440 @@
-use tracing::info;
+use tracing::{debug, info, warn};
 
-use crate::domain::Provider;
-use crate::utils::patterns::{HOST_HEADER_PATTERN, get_field_patterns};
+use crate::domain::{Provider, RequestStep, TransactionMetadata, ZelleBank};
+use crate::error::ZkpError;
+use crate::utils::patterns::{
+    HOST_HEADER_PATTERN, NONCE_FIELD, get_field_patterns, get_nested_field_patterns,
+};
+
+/// Fails with the names in `required` that none of the revealed `fields` carries
+pub fn check_disclosure(fields: &[FieldMatch], required: &[String]) -> Result<(), ZkpError> {
+    let missing: Vec<String> = required
+        .iter()
+        .filter(|name| !fields.iter().any(|field| field.name == name.as_str()))
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ZkpError::InsufficientDisclosure { missing })
+    }
+}
 
 pub fn parse_provider_from_url(url: &str) -> Provider {
     match url {
         s if s.contains("wise.com") => Provider::Wise,
         s if s.contains("paypal.com") => Provider::PayPal,
+        s if s.contains("revolut.com") => Provider::Revolut,
+        s if s.contains("mercadopago.com") => Provider::MercadoPago,
+        s if s.contains("cash.app") => Provider::CashApp,
+        s if s.contains("chase.com") => Provider::Zelle(ZelleBank::Chase),
         _ => Provider::Wise, // Default fallback
     }
 }
 
-pub fn find_field_ranges(response_data: &[u8], provider: &Provider) -> Vec<(usize, usize)> {
-    let (headers, body) = parse_response_data(response_data);
-    let body_start = headers.len();
-    let mut field_ranges = Vec::new();
+/// A payment field located in an HTTP response
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMatch {
+    pub name: &'static str,
+    pub start: usize,
+    pub end: usize,
+    /// Full pattern match; this is the range that gets committed and revealed
+    pub text: String,
+    /// First capture group, else the full match
+    pub value: String,
+    /// Whether every byte of the full match is authenticated. Always true for a prover;
+    /// a verifier's match with its key hidden only places the value by its position,
+    /// which e.g. `sourceAmount` and `targetAmount` share.
+    pub proven: bool,
+}
+
+/// Returns every payment field matched in the response body, sorted by start offset
+pub fn find_fields(response_data: &[u8], provider: &Provider) -> Vec<FieldMatch> {
+    find_transaction_fields(response_data, provider, None)
+}
+
+/// Like `find_fields`, but for array-rooted bodies only searches the element whose `id`
+/// is `transaction_id`. Without a transaction id the whole body is searched.
+///
+/// A received transcript may hold several responses when more than one request was
+/// sent; each is searched in order and offsets stay absolute to the whole transcript.
+/// A field found in an earlier response takes precedence over later matches.
+pub fn find_transaction_fields(
+    response_data: &[u8],
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Vec<FieldMatch> {
+    collect_fields(
+        response_data,
+        split_responses(response_data),
+        provider,
+        transaction_id,
+    )
+}
+
+/// Like `find_transaction_fields`, limited to the responses of request steps that
+/// commit fields
+pub fn find_step_fields(
+    response_data: &[u8],
+    provider: &Provider,
+    transaction_id: Option<&str>,
+    steps: &[RequestStep],
+) -> Vec<FieldMatch> {
+    let spans = step_responses(response_data)
+        .into_iter()
+        .zip(steps)
+        .filter(|(_, step)| step.commit_fields)
+        .map(|(span, _)| span)
+        .collect();
+    collect_fields(response_data, spans, provider, transaction_id)
+}
+
+/// Spans of the responses answering each request step, in step order. A 401 answers
+/// a request that was retried with a refreshed token, so it belongs to no step.
+pub fn step_responses(received: &[u8]) -> Vec<(usize, usize)> {
+    split_responses(received)
+        .into_iter()
+        .filter(|(start, end)| {
+            let status_line = received[*start..*end]
+                .split(|byte| *byte == b'\n')
+                .next()
+                .unwrap_or_default();
+            String::from_utf8_lossy(status_line)
+                .split_whitespace()
+                .nth(1)
+                != Some("401")
+        })
+        .collect()
+}
+
+fn collect_fields(
+    response_data: &[u8],
+    spans: Vec<(usize, usize)>,
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Vec<FieldMatch> {
+    let mut fields: Vec<FieldMatch> = Vec::new();
+
+    for (start, end) in spans {
+        for field in
+            find_response_fields(&response_data[start..end], start, provider, transaction_id)
+        {
+            if !fields.iter().any(|found| found.name == field.name) {
+                fields.push(field);
+            }
+        }
+    }
+
+    fields.sort_by_key(|field| field.start);
+    fields
+}
+
+/// Byte spans of each HTTP response in a received transcript.
+///
+/// Responses are delimited by `Content-Length` when present, otherwise by the next
+/// status line; a trailing partial response extends to the end of the data.
+pub fn split_responses(received: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    while start < received.len() {
+        let Some(header_len) = find_subslice(&received[start..], b"\r\n\r\n").map(|pos| pos + 4)
+        else {
+            spans.push((start, received.len()));
+            break;
+        };
+        let body_start = start + header_len;
+        let headers = String::from_utf8_lossy(&received[start..body_start]);
+
+        let end = match content_length(&headers) {
+            Some(len) => body_start.saturating_add(len).min(received.len()),
+            None => find_subslice(&received[body_start..], b"\r\nHTTP/1.")
+                .map(|pos| body_start + pos + 2)
+                .unwrap_or(received.len()),
+        };
+        spans.push((start, end));
+        start = end;
+    }
+
+    spans
+}
+
+fn content_length(headers: &str) -> Option<usize> {
+    headers.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse().ok())?
+    })
+}
+
+/// Checks each revealed `Content-Length` against the body that follows it in a
+/// verifier's transcript: the body must end exactly at the transcript's end or where a
+/// revealed status line starts the next response. Catches a committed header that
+/// disagrees with a truncated or padded body.
+///
+/// Responses are walked from the start of the transcript; the walk stops at the first
+/// one whose header block or `Content-Length` line isn't fully revealed, since the
+/// following boundaries can't be placed without it.
+pub fn check_content_length(received: &[u8], revealed: &[(usize, usize)]) -> Result<(), ZkpError> {
+    let is_revealed = |start: usize, end: usize| {
+        revealed
+            .iter()
+            .any(|&(range_start, range_end)| range_start <= start && end <= range_end)
+    };
+    let mut start = 0;
+
+    while start < received.len() {
+        let Some(body_start) =
+            find_subslice(&received[start..], b"\r\n\r\n").map(|pos| start + pos + 4)
+        else {
+            return Ok(());
+        };
+        if !is_revealed(body_start - 4, body_start) {
+            return Ok(());
+        }
+        let Some(declared) = revealed_content_length(received, start, body_start, &is_revealed)
+        else {
+            return Ok(());
+        };
+
+        let mismatch = || ZkpError::LengthMismatch {
+            declared,
+            actual: find_subslice(&received[body_start..], b"HTTP/1.")
+                .filter(|&pos| is_revealed(body_start + pos, body_start + pos + 7))
+                .unwrap_or(received.len() - body_start),
+        };
+        // A huge declared length must not wrap around to an earlier response, and the
+        // walk must always move forward
+        let Some(end) = body_start.checked_add(declared).filter(|&end| end > start) else {
+            return Err(mismatch());
+        };
+        let next_response = received
+            .get(end..)
+            .is_some_and(|rest| rest.starts_with(b"HTTP/1.") && is_revealed(end, end + 7));
+        if end == received.len() {
+            return Ok(());
+        }
+        if !next_response {
+            return Err(mismatch());
+        }
+        start = end;
+    }
+
+    Ok(())
+}
+
+/// `Content-Length` of the header block `received[start..body_start]`, when its whole
+/// line is revealed
+fn revealed_content_length(
+    received: &[u8],
+    start: usize,
+    body_start: usize,
+    is_revealed: &dyn Fn(usize, usize) -> bool,
+) -> Option<usize> {
+    let mut line_start = start;
+    for line in received[start..body_start].split(|byte| *byte == b'\n') {
+        let line_end = line_start + line.len();
+        let declared = content_length(&String::from_utf8_lossy(line));
+        if declared.is_some() && is_revealed(line_start, line_end) {
+            return declared;
+        }
+        line_start = line_end + 1;
+    }
+    None
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Fields of a single response; `offset` is the response's position in the transcript.
+///
+/// Matching runs on the raw bytes so offsets stay exact even when the body holds
+/// invalid UTF-8; only the reported text and value are lossily decoded.
+fn find_response_fields(
+    response_data: &[u8],
+    offset: usize,
+    provider: &Provider,
+    transaction_id: Option<&str>,
+) -> Vec<FieldMatch> {
+    let (headers, body) = split_response_data(response_data);
+    let is_array = body
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'[');
+    let (element_start, element_end) = match transaction_id {
+        Some(transaction_id) if is_array => match find_array_element(body, transaction_id) {
+            Some(span) => span,
+            None => {
+                warn!("No array element matches transaction {}", transaction_id);
+                return Vec::new();
+            }
+        },
+        _ => (0, body.len()),
+    };
+    find_body_fields(
+        &body[element_start..element_end],
+        offset + headers.len() + element_start,
+        provider,
+    )
+}
+
+/// The server's echo of a nonce, located by the provider's `nonce_echo_pattern` and
+/// reported as the `nonce` field so it is committed, revealed and verified like any
+/// payment field. Servers often echo it in a header, so the whole transcript is searched.
+pub fn find_nonce_echo(received: &[u8], pattern: &str) -> Option<FieldMatch> {
+    find_field(received, 0, pattern, NONCE_FIELD)
+}
+
+/// Connection preface every HTTP/2 client opens with
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Whether the sent transcript is an HTTP/2 connection
+pub fn is_http2(request_data: &[u8]) -> bool {
+    request_data.starts_with(HTTP2_PREFACE)
+}
+
+/// Payment fields in an HTTP/2 received transcript.
+///
+/// Frames are binary and headers HPACK-compressed, so the whole transcript is searched
+/// as one body. A field split across two DATA frames is not found.
+pub fn find_http2_fields(response_data: &[u8], provider: &Provider) -> Vec<FieldMatch> {
+    let mut fields = find_body_fields(response_data, 0, provider);
+    fields.sort_by_key(|field| field.start);
+    fields
+}
+
+fn find_body_fields(body: &[u8], body_start: usize, provider: &Provider) -> Vec<FieldMatch> {
+    let mut fields = Vec::new();
 
     for (pattern, field_name) in get_field_patterns(provider).iter() {
-        if let Ok(regex) = regex::Regex::new(pattern) {
-            if let Some(captures) = regex.captures(&body) {
-                if let Some(full_match) = captures.get(0) {
-                    let start = body_start + full_match.start();
-                    let end = body_start + full_match.end();
-                    field_ranges.push((start, end));
-                    info!(
-                        "     ✅ Found {}: {} (Bytes {}..{})",
-                        field_name,
-                        full_match.as_str(),
-                        start,
-                        end
-                    );
+        fields.extend(find_field(body, body_start, pattern, field_name));
+    }
+
+    for (object_key, pattern, field_name) in get_nested_field_patterns(provider).iter() {
+        let Some((object_start, object_end)) = find_object(body, object_key) else {
+            debug!("Object {} not found for {}", object_key, field_name);
+            continue;
+        };
+        fields.extend(find_field(
+            &body[object_start..object_end],
+            body_start + object_start,
+            pattern,
+            field_name,
+        ));
+    }
+
+    fields
+}
+
+/// First match of `pattern` in `data`; `offset` is the position of `data` in the transcript
+fn find_field(
+    data: &[u8],
+    offset: usize,
+    pattern: &str,
+    field_name: &'static str,
+) -> Option<FieldMatch> {
+    let regex = regex::bytes::Regex::new(pattern).ok()?;
+    let captures = regex.captures(data)?;
+    let full_match = captures.get(0)?;
+
+    let start = offset + full_match.start();
+    let end = offset + full_match.end();
+    let text = String::from_utf8_lossy(full_match.as_bytes()).into_owned();
+    info!(
+        "     ✅ Found {}: {} (Bytes {}..{})",
+        field_name, text, start, end
+    );
+    let value = captures.get(1).unwrap_or(full_match);
+    Some(FieldMatch {
+        name: field_name,
+        start,
+        end,
+        text,
+        value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        proven: true,
+    })
+}
+
+/// Like `find_fields`, for a verifier's partially redacted transcript. `revealed` are
+/// the authenticated ranges of `received`.
+///
+/// Fields missing from the response layout are searched for in each revealed range
+/// on its own, for presentations from other
+/// TLSNotary clients that don't reveal the headers this crate commits. The nonce echo
+/// is included when the provider has a `nonce_echo_pattern`.
+///
+/// A match with any byte outside `revealed`, such as a hidden key or `unauthed`
+/// filler matched as a value, is returned with `proven` false.
+pub fn find_revealed_fields(
+    received: &[u8],
+    revealed: &[(usize, usize)],
+    provider: &Provider,
+    nonce_echo_pattern: Option<&str>,
+) -> Vec<FieldMatch> {
+    let mut fields = find_fields(received, provider);
+    fields.extend(nonce_echo_pattern.and_then(|pattern| find_nonce_echo(received, pattern)));
+    fields.extend(scan_revealed_ranges(received, revealed, provider, &fields));
+
+    for field in &mut fields {
+        field.proven = revealed
+            .iter()
+            .any(|&(start, end)| start <= field.start && field.end <= end);
+        if !field.proven {
+            warn!(
+                "{} was revealed without its surrounding text, which is unproven",
+                field.name
+            );
+        }
+    }
+    fields.sort_by_key(|field| field.start);
+    fields
+}
+
+/// Fields not in `found`, matched within each revealed range without locating a
+/// response body first. A nested field still needs its object key revealed in the
+/// same range; otherwise it could have been taken from any object with that shape.
+fn scan_revealed_ranges(
+    received: &[u8],
+    revealed: &[(usize, usize)],
+    provider: &Provider,
+    found: &[FieldMatch],
+) -> Vec<FieldMatch> {
+    let mut fields: Vec<FieldMatch> = Vec::new();
+    let is_missing = |name: &str, fields: &[FieldMatch]| {
+        !found.iter().chain(fields).any(|field| field.name == name)
+    };
+
+    for &(start, end) in revealed {
+        let range = &received[start..end];
+        for field in find_body_fields(range, start, provider) {
+            if is_missing(field.name, &fields) {
+                debug!("{} found outside the expected response layout", field.name);
+                fields.push(field);
+            }
+        }
+    }
+
+    fields
+}
+
+/// Byte span of the first JSON object stored under `key`, from `{` through `}`
+fn find_object(body: &[u8], key: &str) -> Option<(usize, usize)> {
+    let key_pattern =
+        regex::bytes::Regex::new(&format!(r#""{}"\s*:\s*\{{"#, regex::escape(key))).ok()?;
+    let open = key_pattern.find(body)?.end() - 1;
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, &byte) in body.iter().enumerate().skip(open) {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((open, index + 1));
                 }
             }
+            _ => {}
         }
     }
 
-    field_ranges
+    None
 }
 
-pub fn find_host_header_range(request_data: &[u8]) -> Option<(usize, usize)> {
+/// Returns the byte ranges of every matched payment field, sorted by start offset.
+///
+/// Ranges nested inside another match are dropped since the enclosing range already
+/// covers them; partially overlapping matches are rejected.
+pub fn find_field_ranges(
+    response_data: &[u8],
+    provider: &Provider,
+) -> Result<Vec<(usize, usize)>, ZkpError> {
+    field_ranges(&find_fields(response_data, provider))
+}
+
+/// Converts matched fields into sorted, non-overlapping byte ranges
+pub fn field_ranges(fields: &[FieldMatch]) -> Result<Vec<(usize, usize)>, ZkpError> {
+    normalize_field_ranges(
+        fields
+            .iter()
+            .map(|field| (field.start, field.end, field.name))
+            .collect(),
+    )
+}
+
+/// Checks every range against a transcript of `len` bytes before it reaches tlsn, whose
+/// own failure wouldn't say which field was at fault. `fields` are the matches the
+/// ranges were built from, used to name the offending one.
+pub fn check_range_bounds(
+    direction: &'static str,
+    ranges: &[(usize, usize)],
+    len: usize,
+    fields: &[FieldMatch],
+) -> Result<(), ZkpError> {
+    let Some(&(start, end)) = ranges.iter().find(|(start, end)| start > end || *end > len) else {
+        return Ok(());
+    };
+
+    let names: Vec<&str> = fields
+        .iter()
+        .filter(|field| field.start < end && start < field.end)
+        .map(|field| field.name)
+        .collect();
+    Err(ZkpError::RangeOutOfBounds {
+        direction,
+        start,
+        end,
+        len,
+        source_fields: if names.is_empty() {
+            "no payment field".to_string()
+        } else {
+            names.join(", ")
+        },
+    })
+}
+
+/// Bytes that only carry JSON structure; a gap made of them discloses no value
+const STRUCTURAL_BYTES: &[u8] = b" \t\r\n,:{}[]\"";
+
+/// Merges neighbouring ranges to shrink the attestation and presentation.
+///
+/// Takes sorted, non-overlapping ranges as returned by `field_ranges`. Two ranges merge
+/// only when the gap between them is at most `max_gap` bytes and consists of JSON
+/// punctuation and whitespace, so merging never reveals a value or key between fields.
+/// A `max_gap` of 0 only merges touching ranges; prove and present must agree on it so
+/// revealed ranges stay within the commitments.
+pub fn coalesce_ranges(
+    data: &[u8],
+    ranges: &[(usize, usize)],
+    max_gap: usize,
+) -> Vec<(usize, usize)> {
+    let mut coalesced: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for &(start, end) in ranges {
+        let mergeable = coalesced.last().is_some_and(|&(_, prev_end)| {
+            start >= prev_end
+                && start - prev_end <= max_gap
+                && data
+                    .get(prev_end..start)
+                    .is_some_and(|gap| gap.iter().all(|byte| STRUCTURAL_BYTES.contains(byte)))
+        });
+        match coalesced.last_mut() {
+            Some(last) if mergeable => last.1 = end,
+            _ => coalesced.push((start, end)),
+        }
+    }
+
+    if coalesced.len() < ranges.len() {
+        debug!("Coalesced {} ranges into {}", ranges.len(), coalesced.len());
+    }
+    coalesced
+}
+
+fn normalize_field_ranges(
+    mut field_ranges: Vec<(usize, usize, &str)>,
+) -> Result<Vec<(usize, usize)>, ZkpError> {
+    // Enclosing ranges sort ahead of the ranges they contain
+    field_ranges.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+
+    let mut normalized: Vec<(usize, usize, &str)> = Vec::with_capacity(field_ranges.len());
+    for (start, end, field_name) in field_ranges {
+        if let Some(&(prev_start, prev_end, prev_name)) = normalized.last() {
+            if end <= prev_end {
+                debug!(
+                    "Field {} ({}..{}) is covered by {} ({}..{})",
+                    field_name, start, end, prev_name, prev_start, prev_end
+                );
+                continue;
+            }
+            if start < prev_end {
+                return Err(ZkpError::OverlappingFields {
+                    first: prev_name.to_string(),
+                    first_range: (prev_start, prev_end),
+                    second: field_name.to_string(),
+                    second_range: (start, end),
+                });
+            }
+        }
+        normalized.push((start, end, field_name));
+    }
+
+    Ok(normalized
+        .into_iter()
+        .map(|(start, end, _)| (start, end))
+        .collect())
+}
+
+/// Byte span of the top-level array element whose `id` equals `transaction_id`
+fn find_array_element(body: &[u8], transaction_id: &str) -> Option<(usize, usize)> {
+    let id_pattern = regex::bytes::Regex::new(&format!(
+        r#""id"\s*:\s*"?{}"?\s*[,}}]"#,
+        regex::escape(transaction_id)
+    ))
+    .ok()?;
+
+    top_level_array_elements(body)
+        .into_iter()
+        .find(|(start, end)| id_pattern.is_match(&body[*start..*end]))
+}
+
+/// Spans of the elements of a top-level JSON array, tracking nesting and string escapes
+fn top_level_array_elements(body: &[u8]) -> Vec<(usize, usize)> {
+    let mut elements = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut element_start = None;
+
+    for (index, &byte) in body.iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth == 2 && byte == b'{' {
+                    element_start = Some(index);
+                }
+            }
+            b']' | b'}' => {
+                let closes_element = depth == 2 && byte == b'}';
+                if let Some(start) = element_start.take_if(|_| closes_element) {
+                    elements.push((start, index + 1));
+                }
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    elements
+}
+
+/// Byte range of the request line (`GET /path HTTP/1.1`), excluding the trailing CRLF.
+/// Revealing it binds the presentation to the requested endpoint and transaction id.
+pub fn find_request_line_range(request_data: &[u8]) -> Option<(usize, usize)> {
+    let end = request_data
+        .windows(2)
+        .position(|window| window == b"\r\n")?;
+    let request_line = String::from_utf8_lossy(&request_data[..end]);
+    if request_line.split_whitespace().count() != 3 {
+        return None;
+    }
+
+    info!("     ✅ Found request line: range 0..{}", end);
+    Some((0, end))
+}
+
+/// Transaction id requested in the sent transcript, taken from the last path segment
+/// of the request line. Endpoint templates all end in `{transaction_id}`.
+pub fn request_transaction_id(request_data: &[u8]) -> Option<String> {
     let request_str = String::from_utf8_lossy(request_data);
+    let target = request_str.lines().next()?.split_whitespace().nth(1)?;
+    last_path_segment(target)
+}
 
-    if let Ok(regex) = regex::Regex::new(HOST_HEADER_PATTERN) {
-        if let Some(host_match) = regex.find(&request_str) {
-            info!(
-                "     ✅ Found host header: range {}..{}",
-                host_match.start(),
-                host_match.end()
-            );
-            return Some((host_match.start(), host_match.end()));
+/// Whether `request_data` requests `url`: the same path and query, and a Host header
+/// and any absolute-form target naming the URL's host
+pub fn request_matches_url(request_data: &[u8], url: &str) -> bool {
+    let Ok(uri) = url.parse::<hyper::Uri>() else {
+        return false;
+    };
+    let request_str = String::from_utf8_lossy(request_data);
+    let target = request_str
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|target| target.parse::<hyper::Uri>().ok());
+    let Some(target) = target else {
+        return false;
+    };
+    let host_header = find_host_header_range(request_data)
+        .map(|(start, end)| String::from_utf8_lossy(&request_data[start + "host: ".len()..end]));
+
+    let same_host = |host: &str| {
+        uri.host()
+            .is_some_and(|expected| host.trim().eq_ignore_ascii_case(expected))
+    };
+    target.path_and_query() == uri.path_and_query()
+        && target.host().is_none_or(same_host)
+        && host_header.is_some_and(|host| same_host(host.split(':').next().unwrap_or_default()))
+}
+
+/// Transaction id in an endpoint URL, matching what `request_transaction_id` reads
+/// back from a transcript requesting it
+pub fn url_transaction_id(url: &str) -> Option<String> {
+    let uri: hyper::Uri = url.parse().ok()?;
+    last_path_segment(uri.path())
+}
+
+fn last_path_segment(target: &str) -> Option<String> {
+    let path = target.split(['?', '#']).next()?;
+
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .map(percent_decode)
+}
+
+/// Reverses the percent-encoding `Provider::transaction_endpoint` applies, so ids
+/// read back from a request target compare equal to the ids that were requested.
+/// Malformed escapes are kept as written. The decoded id is only for comparison;
+/// `file_io::get_transaction_file_path` escapes it again before it names a file.
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
         }
     }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
 
-    None
+pub fn find_host_header_range(request_data: &[u8]) -> Option<(usize, usize)> {
+    let regex = regex::bytes::Regex::new(HOST_HEADER_PATTERN).ok()?;
+    let host_match = regex.find(request_data)?;
+    info!(
+        "     ✅ Found host header: range {}..{}",
+        host_match.start(),
+        host_match.end()
+    );
+    Some((host_match.start(), host_match.end()))
 }
 
-pub fn parse_response_data(response_data: &[u8]) -> (String, String) {
-    let response_str = String::from_utf8_lossy(response_data);
+/// Summarizes the transaction in a received transcript with the provider's extractor
+pub fn analyze_transcript(received: &[u8], provider: &Provider) -> Option<TransactionMetadata> {
+    let fields = find_fields(received, provider);
+    provider.metadata_extractor().extract(&|name| {
+        fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value.clone())
+    })
+}
+
+/// Whether the received transcript was cut off by `max_recv_data`: it fills the limit
+/// and the last response's body is not complete JSON
+pub fn is_truncated(received: &[u8], max_recv_data: usize) -> bool {
+    if received.len() < max_recv_data {
+        return false;
+    }
 
+    let Some(&(start, end)) = split_responses(received).last() else {
+        return true;
+    };
+    let (_, body) = split_response_data(&received[start..end]);
+    serde_json::from_slice::<serde::de::IgnoredAny>(body).is_err()
+}
+
+/// Splits a response into its header section (including the blank line) and body
+/// without decoding, so positions in either slice map directly onto the transcript
+pub fn split_response_data(response_data: &[u8]) -> (&[u8], &[u8]) {
     // Use regex to find the end of HTTP headers (double CRLF or double LF)
-    let separator = regex::Regex::new(r"\r\n\r\n|\n\n").unwrap();
-    if let Some(mat) = separator.find(&response_str) {
-        let header_section = response_str[..mat.end()].to_string();
-        let body_section = response_str[mat.end()..].to_string();
-        return (header_section, body_section);
+    let separator = regex::bytes::Regex::new(r"\r\n\r\n|\n\n").unwrap();
+    if let Some(mat) = separator.find(response_data) {
+        return response_data.split_at(mat.end());
+    }
+
+    // Fallback: treat the entire response as body if no separator found
+    (&[], response_data)
+}
+
+/// Splits a saved request+response fixture into its sent and received parts. Fixture
+/// requests carry no body, so the request ends at its blank line; the rest must be an
+/// HTTP/1.x response.
+pub fn split_fixture(fixture: &[u8]) -> Option<(&[u8], &[u8])> {
+    let request_len = find_subslice(fixture, b"\r\n\r\n")? + 4;
+    let (sent, received) = fixture.split_at(request_len);
+    received.starts_with(b"HTTP/1.").then_some((sent, received))
+}
+
+/// Lossily decoded form of `split_response_data`, for display only; byte offsets into
+/// the returned strings can differ from the transcript when it isn't valid UTF-8
+pub fn parse_response_data(response_data: &[u8]) -> (String, String) {
+    let (headers, body) = split_response_data(response_data);
+    (
+        String::from_utf8_lossy(headers).into_owned(),
+        String::from_utf8_lossy(body).into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WISE_RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"id\":123456789,\"targetRecipientId\":555,\"state\":\"OUTGOING_PAYMENT_SENT\",\"date\":1700000000000,\"targetAmount\":100.5,\"targetCurrency\":\"EUR\"}";
+
+    #[test]
+    fn test_wise_field_ranges_sorted_without_overlaps() {
+        let field_ranges = find_field_ranges(WISE_RESPONSE.as_bytes(), &Provider::Wise).unwrap();
+
+        let fields: Vec<&str> = field_ranges
+            .iter()
+            .map(|(start, end)| &WISE_RESPONSE[*start..*end])
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                r#""id":123456789"#,
+                r#""targetRecipientId":555"#,
+                r#""state":"OUTGOING_PAYMENT_SENT","date":1700000000000"#,
+                r#""targetAmount":100.5"#,
+                r#""targetCurrency":"EUR""#,
+            ]
+        );
+        assert!(field_ranges.windows(2).all(|pair| pair[0].1 <= pair[1].0));
+    }
+
+    #[test]
+    fn test_disclosure_missing_required_fields_rejected() {
+        let response = "HTTP/1.1 200 OK\r\n\r\n\
+            {\"id\":123,\"state\":\"OUTGOING_PAYMENT_SENT\",\"targetAmount\":10.5}";
+        let fields = find_fields(response.as_bytes(), &Provider::Wise);
+        let required = ["paymentId".to_string(), "targetAmount".to_string()];
+        assert!(check_disclosure(&fields, &required).is_ok());
+
+        let required = [
+            "targetAmount".to_string(),
+            "targetCurrency".to_string(),
+            "state".to_string(),
+        ];
+        match check_disclosure(&fields, &required) {
+            Err(ZkpError::InsufficientDisclosure { missing }) => {
+                assert_eq!(missing, vec!["targetCurrency".to_string()])
+            }
+            other => panic!("expected InsufficientDisclosure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_range_bounds_name_the_field() {
+        let fields = find_fields(WISE_RESPONSE.as_bytes(), &Provider::Wise);
+        let ranges = field_ranges(&fields).unwrap();
+        assert!(check_range_bounds("Received", &ranges, WISE_RESPONSE.len(), &fields).is_ok());
+
+        // As if the transcript were cut off inside the currency field
+        let len = WISE_RESPONSE.len() - 3;
+        let error = check_range_bounds("Received", &ranges, len, &fields).unwrap_err();
+        assert!(matches!(
+            &error,
+            ZkpError::RangeOutOfBounds { source_fields, .. } if source_fields == "targetCurrency"
+        ));
+        assert!(error.to_string().starts_with("Received range "));
+    }
+
+    #[test]
+    fn test_split_fixture_at_request_head() {
+        let request =
+            "GET /gateway/v3/profiles/1/transfers/123456789 HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        let fixture = format!("{}{}", request, WISE_RESPONSE);
+
+        let (sent, received) = split_fixture(fixture.as_bytes()).unwrap();
+        assert_eq!(sent, request.as_bytes());
+        assert_eq!(received, WISE_RESPONSE.as_bytes());
+        assert_eq!(request_transaction_id(sent).as_deref(), Some("123456789"));
+
+        assert!(split_fixture(request.as_bytes()).is_none());
+        assert!(split_fixture(WISE_RESPONSE.as_bytes()).is_none());
+    }
+
+    #[test]
+    fn test_field_value_is_first_capture_group() {
+        let fields = find_fields(WISE_RESPONSE.as_bytes(), &Provider::Wise);
+        let state = fields.iter().find(|field| field.name == "state").unwrap();
+
+        assert_eq!(state.text, r#""state":"OUTGOING_PAYMENT_SENT""#);
+        assert_eq!(state.value, "OUTGOING_PAYMENT_SENT");
+        assert_eq!(&WISE_RESPONSE[state.start..state.end], state.text);
     }
 
-    // Fallback: return entire response as header if no separator found
-    (String::new(), response_str.to_string())
+    #[test]
+    fn test_wise_conversion_fields_extracted_when_present() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"id":987,"state":"OUTGOING_PAYMENT_SENT","date":1700000000000,"#,
+            r#""sourceAmount":110.42,"sourceCurrency":"USD","fee":1.12,"rate":0.9201,"#,
+            r#""targetAmount":100.57,"targetCurrency":"EUR"}"#
+        );
+        let fields = find_fields(response.as_bytes(), &Provider::Wise);
+        let value = |name: &str| {
+            fields
+                .iter()
+                .find(|field| field.name == name)
+                .map(|field| field.value.as_str())
+        };
+
+        assert_eq!(value("sourceAmount"), Some("110.42"));
+        assert_eq!(value("sourceCurrency"), Some("USD"));
+        assert_eq!(value("fee"), Some("1.12"));
+        assert_eq!(value("rate"), Some("0.9201"));
+        assert_eq!(value("targetAmount"), Some("100.57"));
+
+        // Same-currency transfers omit them without affecting the other fields
+        let fields = find_fields(WISE_RESPONSE.as_bytes(), &Provider::Wise);
+        assert!(!fields.iter().any(|field| field.name == "rate"));
+        assert!(fields.iter().any(|field| field.name == "targetAmount"));
+    }
+
+    #[test]
+    fn test_array_response_selects_matching_transaction() {
+        let response = "HTTP/1.1 200 OK\r\n\r\n[{\"id\":111,\"targetAmount\":5,\"note\":\"}]\"},{\"id\":222,\"targetAmount\":7.25}]";
+
+        let fields = find_transaction_fields(response.as_bytes(), &Provider::Wise, Some("222"));
+        let values: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.name, field.value.as_str()))
+            .collect();
+        assert_eq!(values, vec![("paymentId", "222"), ("targetAmount", "7.25")]);
+
+        let amount = &fields[1];
+        assert_eq!(
+            &response[amount.start..amount.end],
+            r#""targetAmount":7.25"#
+        );
+
+        assert!(
+            find_transaction_fields(response.as_bytes(), &Provider::Wise, Some("333")).is_empty()
+        );
+
+        let request =
+            b"GET /gateway/v3/profiles/1/transfers/222?x=1 HTTP/1.1\r\nHost: wise.com\r\n\r\n";
+        assert_eq!(request_transaction_id(request).as_deref(), Some("222"));
+    }
+
+    #[test]
+    fn test_url_transaction_id_matches_request_line() {
+        let url = "https://wise.com/gateway/v3/profiles/1/transfers/987654321?full=true";
+        let request = b"GET /gateway/v3/profiles/1/transfers/987654321?full=true HTTP/1.1\r\n";
+        assert_eq!(url_transaction_id(url).as_deref(), Some("987654321"));
+        assert_eq!(request_transaction_id(request), url_transaction_id(url));
+    }
+
+    #[test]
+    fn test_request_matches_url_compares_whole_target() {
+        let url = "https://wise.com/gateway/v3/profiles/1/transfers/987?full=true";
+        let request = b"GET /gateway/v3/profiles/1/transfers/987?full=true HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        assert!(request_matches_url(request, url));
+
+        let absolute = b"GET https://wise.com/gateway/v3/profiles/1/transfers/987?full=true HTTP/1.1\r\nhost: wise.com:443\r\n\r\n";
+        assert!(request_matches_url(absolute, url));
+
+        // Same transaction id under another profile, query or host
+        let other_profile = b"GET /gateway/v3/profiles/2/transfers/987?full=true HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        assert!(!request_matches_url(other_profile, url));
+        let other_query =
+            b"GET /gateway/v3/profiles/1/transfers/987 HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        assert!(!request_matches_url(other_query, url));
+        let other_host = b"GET /gateway/v3/profiles/1/transfers/987?full=true HTTP/1.1\r\nhost: evil.example\r\n\r\n";
+        assert!(!request_matches_url(other_host, url));
+    }
+
+    #[test]
+    fn test_transaction_id_percent_decoded() {
+        let endpoint = Provider::Wise
+            .transaction_endpoint(Some("1"), "tx 42/é")
+            .unwrap();
+        let path = endpoint.split_once("wise.com").unwrap().1;
+        let request = format!("GET {} HTTP/1.1\r\nHost: wise.com\r\n\r\n", path);
+
+        assert!(path.contains("tx%2042%2F%C3%A9"));
+        assert_eq!(
+            request_transaction_id(request.as_bytes()).as_deref(),
+            Some("tx 42/é")
+        );
+        assert_eq!(url_transaction_id(&endpoint).as_deref(), Some("tx 42/é"));
+        assert_eq!(
+            request_transaction_id(b"GET /transfers/50%25%zz HTTP/1.1\r\n").as_deref(),
+            Some("50%%zz")
+        );
+    }
+
+    #[test]
+    fn test_request_line_range_excludes_crlf() {
+        let request = b"GET /transfers/222 HTTP/1.1\r\nhost: wise.com\r\n\r\n";
+        assert_eq!(find_request_line_range(request), Some((0, 27)));
+        assert_eq!(find_request_line_range(b"XXXXXXXX\r\n"), None);
+    }
+
+    #[test]
+    fn test_fields_in_second_response_use_absolute_offsets() {
+        let first = "HTTP/1.1 200 OK\r\nContent-Length: 14\r\n\r\n{\"profile\":42}";
+        let transcript = format!("{}{}", first, WISE_RESPONSE);
+
+        assert_eq!(
+            split_responses(transcript.as_bytes()),
+            vec![(0, first.len()), (first.len(), transcript.len())]
+        );
+
+        let fields = find_fields(transcript.as_bytes(), &Provider::Wise);
+        let amount = fields
+            .iter()
+            .find(|field| field.name == "targetAmount")
+            .unwrap();
+        assert!(amount.start > first.len());
+        assert_eq!(&transcript[amount.start..amount.end], amount.text);
+        assert_eq!(amount.value, "100.5");
+    }
+
+    #[test]
+    fn test_step_fields_only_from_committing_steps() {
+        let rejected = "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n";
+        let list = "HTTP/1.1 200 OK\r\nContent-Length: 20\r\n\r\n{\"targetAmount\":1.0}";
+        let transcript = format!("{}{}{}", rejected, list, WISE_RESPONSE);
+
+        let details = RequestStep::transaction(Provider::Wise.endpoint_template());
+        let steps = [
+            RequestStep {
+                commit_fields: false,
+                ..details.clone()
+            },
+            details,
+        ];
+        assert_eq!(step_responses(transcript.as_bytes()).len(), 2);
+
+        let fields = find_step_fields(transcript.as_bytes(), &Provider::Wise, None, &steps);
+        let amount = fields
+            .iter()
+            .find(|field| field.name == "targetAmount")
+            .unwrap();
+        assert_eq!(amount.value, "100.5");
+        assert!(amount.start > rejected.len() + list.len());
+    }
+
+    #[test]
+    fn test_truncation_detected_only_at_the_limit() {
+        let response = WISE_RESPONSE.as_bytes();
+        assert!(!is_truncated(response, response.len()));
+        assert!(!is_truncated(response, response.len() + 1));
+
+        let cut = &response[..response.len() - 10];
+        assert!(is_truncated(cut, cut.len()));
+        assert!(!is_truncated(cut, cut.len() + 1));
+    }
+
+    #[test]
+    fn test_http2_fields_found_in_data_frames() {
+        let mut sent = HTTP2_PREFACE.to_vec();
+        sent.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0, 0]);
+        assert!(is_http2(&sent));
+        assert!(!is_http2(b"GET / HTTP/1.1\r\n\r\n"));
+
+        // A SETTINGS frame, then a DATA frame carrying the JSON body
+        let body = br#"{"id":42,"targetAmount":100.5,"targetCurrency":"EUR"}"#;
+        let mut received = vec![0, 0, 0, 4, 0, 0, 0, 0, 0];
+        received.extend_from_slice(&[0, 0, body.len() as u8, 0, 1, 0, 0, 0, 1]);
+        received.extend_from_slice(body);
+
+        let fields = find_http2_fields(&received, &Provider::Wise);
+        let amount = fields
+            .iter()
+            .find(|field| field.name == "targetAmount")
+            .unwrap();
+        assert_eq!(amount.value, "100.5");
+        assert_eq!(&received[amount.start..amount.end], amount.text.as_bytes());
+    }
+
+    #[test]
+    fn test_revolut_fields_extracted_from_fixture() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"[{"id":"65f1c2a0-1111-a000-9b2c-6d1f0e7a9c31","legId":"65f1c2a0-1111-a001","#,
+            r#""type":"TRANSFER","state":"COMPLETED","startedDate":1710336000000,"#,
+            r#""completedDate":1710336012345,"amount":-2500,"currency":"EUR","#,
+            r#""description":"To Alice"}]"#
+        );
+
+        let fields = find_transaction_fields(
+            response.as_bytes(),
+            &Provider::Revolut,
+            Some("65f1c2a0-1111-a000-9b2c-6d1f0e7a9c31"),
+        );
+        let values: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.name, field.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("transactionId", "65f1c2a0-1111-a000-9b2c-6d1f0e7a9c31"),
+                ("state", "COMPLETED"),
+                ("completedDate", "1710336012345"),
+                ("amount", "-2500"),
+                ("currency", "EUR"),
+            ]
+        );
+        assert!(field_ranges(&fields).is_ok());
+        assert_eq!(
+            parse_provider_from_url("https://app.revolut.com/api/retail/transaction/1"),
+            Provider::Revolut
+        );
+    }
+
+    #[test]
+    fn test_mercadopago_fields_extracted_from_fixture() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"id":73412345678,"date_created":"2024-03-13T10:00:00.000-04:00","#,
+            r#""date_approved":"2024-03-13T10:00:02.000-04:00","operation_type":"money_transfer","#,
+            r#""status":"approved","status_detail":"accredited","currency_id":"BRL","#,
+            r#""transaction_amount":150.75,"payer":{"id":"1234"}}"#
+        );
+
+        let fields = find_fields(response.as_bytes(), &Provider::MercadoPago);
+        let values: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.name, field.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("paymentId", "73412345678"),
+                ("date_approved", "2024-03-13T10:00:02.000-04:00"),
+                ("status", "approved"),
+                ("currency_id", "BRL"),
+                ("transaction_amount", "150.75"),
+            ]
+        );
+        assert!(field_ranges(&fields).is_ok());
+
+        let metadata = analyze_transcript(response.as_bytes(), &Provider::MercadoPago).unwrap();
+        assert_eq!(metadata.amount, "150.75");
+        assert_eq!(metadata.currency, "BRL");
+        assert_eq!(
+            parse_provider_from_url("https://api.mercadopago.com/v1/payments/73412345678"),
+            Provider::MercadoPago
+        );
+    }
+
+    #[test]
+    fn test_cashapp_fields_extracted_from_fixture() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"token":"PAY_8f3k2m9q1x","status":"COMPLETED","#,
+            r#""total_amount":{"amount":2550,"currency_code":"USD"},"#,
+            r#""created_at":1710338400000,"recipient":{"cashtag":"$alice"}}"#
+        );
+
+        let fields = find_fields(response.as_bytes(), &Provider::CashApp);
+        let values: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.name, field.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("paymentToken", "PAY_8f3k2m9q1x"),
+                ("status", "COMPLETED"),
+                ("amount", "2550"),
+                ("currency", "USD"),
+                ("createdAt", "1710338400000"),
+            ]
+        );
+        assert!(field_ranges(&fields).is_ok());
+
+        let metadata = analyze_transcript(response.as_bytes(), &Provider::CashApp).unwrap();
+        assert_eq!(metadata.amount, "25.50");
+        assert_eq!(metadata.currency, "USD");
+        assert_eq!(
+            parse_provider_from_url("https://cash.app/api/v1/activity/PAY_8f3k2m9q1x"),
+            Provider::CashApp
+        );
+    }
+
+    #[test]
+    fn test_transcript_metadata_is_provider_agnostic() {
+        let wise = analyze_transcript(WISE_RESPONSE.as_bytes(), &Provider::Wise).unwrap();
+        assert_eq!(
+            wise,
+            TransactionMetadata {
+                id: "123456789".to_string(),
+                amount: "100.5".to_string(),
+                currency: "EUR".to_string(),
+                status: "OUTGOING_PAYMENT_SENT".to_string(),
+                date: Some("1700000000000".to_string()),
+            }
+        );
+
+        let revolut = concat!(
+            "HTTP/1.1 200 OK\r\n\r\n",
+            r#"{"id":"65f1c2a0-1111-a000-9b2c-6d1f0e7a9c31","state":"COMPLETED","#,
+            r#""amount":-2500,"currency":"EUR"}"#
+        );
+        let revolut = analyze_transcript(revolut.as_bytes(), &Provider::Revolut).unwrap();
+        assert_eq!(revolut.amount, "25.00");
+        assert_eq!(revolut.date, None);
+
+        let paypal = "HTTP/1.1 200 OK\r\n\r\n{\"status\":\"COMPLETED\"}";
+        assert!(analyze_transcript(paypal.as_bytes(), &Provider::PayPal).is_none());
+    }
+
+    #[test]
+    fn test_chase_zelle_fields_extracted_from_fixture() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"paymentId":"24681357902","status":"COMPLETED","amount":25.00,"#,
+            r#""recipientName":"Alice","recipientToken":"alice@example.com","#,
+            r#""sendDate":"20240313","memo":"rent"}"#
+        );
+        let provider = parse_provider_from_url(
+            "https://secure.chase.com/svc/rr/payments/secure/v1/quickpay/payment/activity/detail/24681357902",
+        );
+        assert_eq!(provider, Provider::Zelle(ZelleBank::Chase));
+
+        let fields = find_fields(response.as_bytes(), &provider);
+        let values: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.name, field.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("paymentId", "24681357902"),
+                ("status", "COMPLETED"),
+                ("amount", "25.00"),
+                ("recipientToken", "alice@example.com"),
+                ("sendDate", "20240313"),
+            ]
+        );
+        assert!(field_ranges(&fields).is_ok());
+
+        let metadata = analyze_transcript(response.as_bytes(), &provider).unwrap();
+        assert_eq!(metadata.currency, "USD");
+        assert_eq!(provider.to_string(), "zelle-chase");
+    }
+
+    #[test]
+    fn test_field_offsets_exact_with_invalid_utf8() {
+        let mut response = b"HTTP/1.1 200 OK\r\n\r\n{\"note\":\"\xff\xfe\",".to_vec();
+        response.extend_from_slice(br#""targetAmount":42.5}"#);
+
+        let fields = find_fields(&response, &Provider::Wise);
+        let amount = fields
+            .iter()
+            .find(|field| field.name == "targetAmount")
+            .unwrap();
+        assert_eq!(
+            &response[amount.start..amount.end],
+            br#""targetAmount":42.5"#
+        );
+        assert_eq!(amount.value, "42.5");
+    }
+
+    #[test]
+    fn test_paypal_money_object_fields_committed_separately() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n",
+            r#"{"transactionId":"8AB12345CD6789012","status":"COMPLETED","#,
+            r#""fee":{"value":"0.59","currency_code":"EUR"},"#,
+            r#""amount":{"value":"10.00","currency_code":"USD"}}"#
+        );
+
+        let fields = find_fields(response.as_bytes(), &Provider::PayPal);
+        let amount = fields.iter().find(|field| field.name == "amount").unwrap();
+        let currency = fields
+            .iter()
+            .find(|field| field.name == "currency")
+            .unwrap();
+
+        assert_eq!(&response[amount.start..amount.end], r#""value":"10.00""#);
+        assert_eq!(amount.value, "10.00");
+        assert_eq!(
+            &response[currency.start..currency.end],
+            r#""currency_code":"USD""#
+        );
+        assert_eq!(currency.value, "USD");
+        assert!(amount.end <= currency.start);
+        assert_eq!(field_ranges(&fields).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_revealed_ranges_scanned_without_headers() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Length: 81\r\n\r\n",
+            r#"{"id":"5X","amount":{"value":"25.00","currency_code":"USD"},"status":"COMPLETED"}"#,
+        );
+        // Another client revealed the amount object and status but not the headers,
+        // so the body can't be located
+        let revealed: Vec<(usize, usize)> = [
+            r#""amount":{"value":"25.00","currency_code":"USD"}"#,
+            r#""status":"COMPLETED""#,
+        ]
+        .iter()
+        .map(|text| {
+            let start = response.find(text).unwrap();
+            (start, start + text.len())
+        })
+        .collect();
+        let mut redacted = vec![b'X'; response.len()];
+        for &(start, end) in &revealed {
+            redacted[start..end].copy_from_slice(&response.as_bytes()[start..end]);
+        }
+
+        let fields = find_revealed_fields(&redacted, &revealed, &Provider::PayPal, None);
+        let value = |name: &str| {
+            fields
+                .iter()
+                .find(|field| field.name == name)
+                .map(|field| field.value.as_str())
+        };
+        assert_eq!(value("amount"), Some("25.00"));
+        assert_eq!(value("currency"), Some("USD"));
+        assert_eq!(value("status"), Some("COMPLETED"));
+    }
+
+    #[test]
+    fn test_nested_field_without_revealed_object_rejected() {
+        let response = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Length: 116\r\n\r\n",
+            r#"{"fee":{"value":"1.00","currency_code":"USD"},"#,
+            r#""amount":{"value":"25.00","currency_code":"USD"},"status":"COMPLETED"}"#,
+        );
+        // Only the fee object's contents are revealed, a decoy with the amount's shape
+        let decoy = r#""value":"1.00","currency_code":"USD""#;
+        let start = response.find(decoy).unwrap();
+        let revealed = [(start, start + decoy.len())];
+        let mut redacted = vec![b'X'; response.len()];
+        redacted[start..start + decoy.len()]
+            .copy_from_slice(&response.as_bytes()[start..start + decoy.len()]);
+
+        let fields = find_revealed_fields(&redacted, &revealed, &Provider::PayPal, None);
+        assert!(fields.iter().all(|field| field.name != "amount"));
+        assert!(fields.iter().all(|field| field.name != "currency"));
+    }
+
+    #[test]
+    fn test_match_with_hidden_bytes_unproven() {
+        let response = WISE_RESPONSE.as_bytes();
+        let amount = find_fields(response, &Provider::Wise)
+            .into_iter()
+            .find(|field| field.name == "targetAmount")
+            .unwrap();
+        let state = WISE_RESPONSE.find("OUTGOING_PAYMENT_SENT").unwrap();
+        // The amount is revealed whole; the state's value is hidden, so its pattern
+        // only matches the filler between the revealed key and closing quote
+        let revealed = vec![
+            (amount.start, amount.end),
+            (state - 9, state),
+            (state + 21, state + 22),
+        ];
+        let mut redacted = vec![b'X'; response.len()];
+        for &(start, end) in &revealed {
+            redacted[start..end].copy_from_slice(&response[start..end]);
+        }
+
+        let fields = find_revealed_fields(&redacted, &revealed, &Provider::Wise, None);
+        let field = |name: &str| fields.iter().find(|field| field.name == name).unwrap();
+        assert!(field("targetAmount").proven);
+        assert_eq!(field("state").value, "X".repeat(21));
+        assert!(!field("state").proven);
+    }
+
+    #[test]
+    fn test_content_length_checked_against_revealed_body() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let all = [(0, response.len())];
+        assert!(check_content_length(response, &all).is_ok());
+
+        // Two responses back to back, each consistent with its header
+        let two = [&response[..], &response[..]].concat();
+        assert!(check_content_length(&two, &[(0, two.len())]).is_ok());
+
+        let truncated = &response[..response.len() - 1];
+        assert!(matches!(
+            check_content_length(truncated, &[(0, truncated.len())]),
+            Err(ZkpError::LengthMismatch {
+                declared: 5,
+                actual: 4
+            })
+        ));
+
+        let padded = [&response[..], b"!"].concat();
+        assert!(matches!(
+            check_content_length(&padded, &[(0, padded.len())]),
+            Err(ZkpError::LengthMismatch {
+                declared: 5,
+                actual: 6
+            })
+        ));
+
+        // A hidden Content-Length line proves nothing, so it isn't checked
+        assert!(check_content_length(&padded, &[(0, 17), (36, padded.len())]).is_ok());
+    }
+
+    #[test]
+    fn test_huge_content_length_rejected() {
+        let huge = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\nhello",
+            usize::MAX
+        );
+        assert!(matches!(
+            check_content_length(huge.as_bytes(), &[(0, huge.len())]),
+            Err(ZkpError::LengthMismatch {
+                declared: usize::MAX,
+                actual: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_coalescing_only_bridges_structural_gaps() {
+        let data = br#"{"a":"1","b":"2","c":"secret","d":"4"}"#;
+        // "a":"1" and "b":"2" are separated by a comma; "c" sits between "b" and "d"
+        let ranges = [(1, 8), (9, 16), (30, 37)];
+
+        assert_eq!(coalesce_ranges(data, &ranges, 0), ranges.to_vec());
+        assert_eq!(coalesce_ranges(data, &ranges, 1), vec![(1, 16), (30, 37)]);
+        // A wide threshold still refuses to reveal the value in between
+        assert_eq!(coalesce_ranges(data, &ranges, 64), vec![(1, 16), (30, 37)]);
+        // Touching ranges always merge
+        assert_eq!(coalesce_ranges(data, &[(1, 8), (8, 9)], 0), vec![(1, 9)]);
+    }
+
+    #[test]
+    fn test_partially_overlapping_fields_rejected() {
+        let result = normalize_field_ranges(vec![(10, 20, "amount"), (0, 12, "id")]);
+
+        match result {
+            Err(ZkpError::OverlappingFields { first, second, .. }) => {
+                assert_eq!(first, "id");
+                assert_eq!(second, "amount");
+            }
+            other => panic!("expected overlap error, got {:?}", other),
+        }
+    }
 }
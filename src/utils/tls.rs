@@ -1,15 +1,114 @@
 use anyhow::Error;
 use http_body_util::Empty;
-use hyper::{Request, body::Bytes};
+use hyper::client::conn::{http1, http2};
+use hyper::{Request, Response, Version, body::Bytes, body::Incoming};
+use hyper_util::rt::TokioExecutor;
 use tracing::debug;
 
-/// Builds an HTTP request with common headers for TLSNotary attestation
+use crate::domain::{HttpVersion, ServerConfig};
+
+/// Headers whose values never appear in logs unless configured otherwise
+pub const DEFAULT_REDACTED_HEADERS: &[&str] = &["cookie", "x-access-token", "authorization"];
+
+fn loggable_header_value<'a>(name: &str, value: &'a str, redacted: &[String]) -> &'a str {
+    let is_redacted = if redacted.is_empty() {
+        DEFAULT_REDACTED_HEADERS
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name))
+    } else {
+        redacted
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name))
+    };
+
+    if is_redacted { "[REDACTED]" } else { value }
+}
+
+/// Value of the `Connection` header sent with a request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionMode {
+    /// Closes the connection after the response so the transcript finalizes
+    Close,
+    /// Keeps the MPC-TLS connection open for a follow-up request
+    KeepAlive,
+}
+
+impl ConnectionMode {
+    /// Keeps the connection alive between requests of a flow, closing it on the last one
+    pub fn for_request(is_final: bool) -> Self {
+        if is_final {
+            ConnectionMode::Close
+        } else {
+            ConnectionMode::KeepAlive
+        }
+    }
+
+    pub fn header_value(&self) -> &'static str {
+        match self {
+            ConnectionMode::Close => "close",
+            ConnectionMode::KeepAlive => "keep-alive",
+        }
+    }
+}
+
+/// Request half of an HTTP connection, in whichever version the server is configured for
+pub enum RequestSender {
+    Http1(http1::SendRequest<Empty<Bytes>>),
+    Http2(http2::SendRequest<Empty<Bytes>>),
+}
+
+impl RequestSender {
+    /// Starts HTTP on `io`, driving the connection on a background task that ends once
+    /// the sender is dropped or the socket closes
+    pub async fn handshake<T>(io: T, version: HttpVersion) -> Result<Self, Error>
+    where
+        T: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+    {
+        match version {
+            HttpVersion::Http10 | HttpVersion::Http11 => {
+                let (sender, connection) = http1::handshake(io).await?;
+                tokio::spawn(connection);
+                Ok(RequestSender::Http1(sender))
+            }
+            HttpVersion::Http2 => {
+                let (sender, connection) = http2::handshake(TokioExecutor::new(), io).await?;
+                tokio::spawn(connection);
+                Ok(RequestSender::Http2(sender))
+            }
+        }
+    }
+
+    pub async fn send_request(
+        &mut self,
+        mut request: Request<Empty<Bytes>>,
+    ) -> hyper::Result<Response<Incoming>> {
+        match self {
+            RequestSender::Http1(sender) => sender.send_request(request).await,
+            RequestSender::Http2(sender) => {
+                // HTTP/2 carries the host as :authority and forbids connection headers
+                *request.version_mut() = Version::HTTP_2;
+                request.headers_mut().remove(hyper::header::HOST);
+                request.headers_mut().remove(hyper::header::CONNECTION);
+                sender.send_request(request).await
+            }
+        }
+    }
+}
+
+/// Builds an HTTP request with common headers for TLSNotary attestation.
+///
+/// `Host`, `Accept` and `Accept-Language` come from `server`, which also lists the
+/// headers masked in the debug log. The request line carries `version`; HTTP/2
+/// requests are built as HTTP/1.1 and converted by `RequestSender`, which owns the
+/// connection's framing.
 pub fn build_request(
     url: &str,
-    server_name: &str,
+    server: &ServerConfig,
     extra_headers: &[(&str, &str)],
     description: &str,
     user_agent: &str,
+    connection: ConnectionMode,
+    version: HttpVersion,
 ) -> Result<Request<Empty<Bytes>>, Error> {
     debug!("Building HTTP request: {} -> {}", description, url);
 
@@ -18,13 +117,137 @@ pub fn build_request(
     let request_builder = extra_headers.iter().fold(
         Request::builder()
             .uri(url)
-            .header("Host", server_name)
-            .header("Accept", "*/*")
+            .version(match version {
+                HttpVersion::Http10 => Version::HTTP_10,
+                HttpVersion::Http11 | HttpVersion::Http2 => Version::HTTP_11,
+            })
+            .header("Host", &server.host)
+            .header("Accept", server.accept())
+            .header("Accept-Language", server.accept_language())
             .header("Accept-Encoding", "identity")
-            .header("Connection", "close")
+            .header("Connection", connection.header_value())
             .header("User-Agent", user_agent),
-        |builder, (key, value)| builder.header(*key, *value),
+        |builder, (key, value)| {
+            debug!(
+                "  {}: {}",
+                key,
+                loggable_header_value(key, value, &server.redacted_headers)
+            );
+            builder.header(*key, *value)
+        },
     );
 
     Ok(request_builder.body(Empty::<Bytes>::new())?)
 }
+
+/// Bytes `request` takes on the wire as HTTP/1.x: request line, headers and the blank
+/// line. HTTP/2 compresses headers, so this bounds its size too.
+pub fn serialized_request_len(request: &Request<Empty<Bytes>>) -> usize {
+    let request_line = request.method().as_str().len()
+        + 1
+        + request.uri().to_string().len()
+        + " HTTP/1.1\r\n".len();
+    let headers: usize = request
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + ": ".len() + value.len() + "\r\n".len())
+        .sum();
+    request_line + headers + "\r\n".len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiation_headers_follow_server_config() {
+        let server = ServerConfig {
+            accept_language: Some("de-DE".to_string()),
+            ..ServerConfig::new("negotiation.example", 443)
+        };
+        let request = build_request(
+            "https://negotiation.example/",
+            &server,
+            &[],
+            "test",
+            "test-agent",
+            ConnectionMode::Close,
+            HttpVersion::Http11,
+        )
+        .unwrap();
+        assert_eq!(request.headers()["host"], "negotiation.example");
+        assert_eq!(request.headers()["accept"], "*/*");
+        assert_eq!(request.headers()["accept-language"], "de-DE");
+
+        let request = build_request(
+            "https://other.example/",
+            &ServerConfig::new("other.example", 443),
+            &[],
+            "test",
+            "test-agent",
+            ConnectionMode::Close,
+            HttpVersion::Http11,
+        )
+        .unwrap();
+        assert_eq!(
+            request.headers()["accept-language"],
+            crate::domain::DEFAULT_ACCEPT_LANGUAGE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_line_carries_configured_version() {
+        use tokio::io::AsyncReadExt;
+
+        let (client, mut server) = tokio::io::duplex(4096);
+        let mut sender =
+            RequestSender::handshake(hyper_util::rt::TokioIo::new(client), HttpVersion::Http10)
+                .await
+                .unwrap();
+        let request = build_request(
+            "https://api.example/v1/payments/42",
+            &ServerConfig::new("api.example", 443),
+            &[],
+            "test",
+            "test-agent",
+            ConnectionMode::Close,
+            HttpVersion::Http10,
+        )
+        .unwrap();
+        // No response ever comes; only the bytes written to the socket matter
+        tokio::spawn(async move { sender.send_request(request).await });
+
+        let mut sent = Vec::new();
+        while !sent.ends_with(b"\r\n\r\n") {
+            let mut buf = [0u8; 1024];
+            let n = server.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed before the request was written");
+            sent.extend_from_slice(&buf[..n]);
+        }
+
+        let request_line = b"GET https://api.example/v1/payments/42 HTTP/1.0";
+        assert!(sent.starts_with(request_line));
+        assert_eq!(
+            crate::utils::text_parser::find_request_line_range(&sent),
+            Some((0, request_line.len()))
+        );
+        assert_eq!(
+            crate::utils::text_parser::request_transaction_id(&sent).as_deref(),
+            Some("42")
+        );
+    }
+
+    #[test]
+    fn test_redaction_list_is_case_insensitive() {
+        let secret = "session=abc";
+        assert_eq!(loggable_header_value("Cookie", secret, &[]), "[REDACTED]");
+        assert_eq!(loggable_header_value("X-Api-Key", secret, &[]), secret);
+
+        let custom = vec!["x-api-key".to_string()];
+        assert_eq!(
+            loggable_header_value("X-API-KEY", secret, &custom),
+            "[REDACTED]"
+        );
+        assert_eq!(loggable_header_value("Accept", "*/*", &custom), "*/*");
+    }
+}
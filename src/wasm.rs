@@ -0,0 +1,45 @@
+//! Browser entry point for verifying presentations, enabled with the `wasm` feature.
+//!
+//! Only `verify_bytes` is reachable from here, which needs neither a filesystem nor an
+//! async runtime. Building for `wasm32-unknown-unknown` additionally requires a
+//! tlsn-core build that targets it.
+
+use clap::ValueEnum;
+use wasm_bindgen::prelude::*;
+
+use crate::domain::{ArtifactFormat, Provider, VerifyOptions};
+
+/// Byte shown in place of redacted transcript bytes, as in the default config
+const UNAUTHED_BYTES: &str = "X";
+
+/// Verifies a bincode presentation for `provider` (e.g. `wise`) and returns the
+/// verification report as a JS object. Errors are thrown as strings. Without a
+/// provider it is detected from the presentation's server name.
+///
+/// No wall-clock checks are applied, so the same bytes verify the same on any machine.
+#[wasm_bindgen]
+pub fn verify_presentation_wasm(
+    bytes: &[u8],
+    provider: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let provider = provider
+        .map(|provider| {
+            Provider::from_str(&provider, true)
+                .map_err(|_| JsValue::from_str(&format!("Unknown provider {:?}", provider)))
+        })
+        .transpose()?;
+    let options = VerifyOptions {
+        offline: true,
+        ..VerifyOptions::default()
+    };
+
+    let report = crate::verify_bytes(
+        provider.as_ref(),
+        bytes,
+        UNAUTHED_BYTES,
+        ArtifactFormat::Bincode,
+        &options,
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    serde_wasm_bindgen::to_value(&report).map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -84,6 +84,7 @@ int main() {
         "/test",
         NULL,
         NULL,
+        NULL,  // No extra headers
         test_user_agent,
         test_provider_host,
         test_provider_port,
@@ -102,7 +103,7 @@ int main() {
 
     // Test invalid provider URL (nonexistent presentation file)
     printf("\n3. Testing tlsn_verify() with nonexistent provider...\n");
-    result = tlsn_verify("nonexistent.com", test_unauthed_bytes);
+    result = tlsn_verify("nonexistent.com", test_unauthed_bytes, NULL, NULL);
     if (result != 0) {
         printf("   ✅ Nonexistent provider properly rejected with code: %d\n", result);
         print_error_if_available();
@@ -110,6 +111,43 @@ int main() {
         printf("   ❌ Nonexistent provider should have been rejected\n");
     }
 
+    // Test provider listing
+    printf("\n4. Testing tlsn_supported_providers()...\n");
+    const char* providers = tlsn_supported_providers();
+    if (providers) {
+        printf("   ✅ Supported providers: %s\n", providers);
+        tlsn_free_string((char*)providers);
+    } else {
+        printf("   ❌ Failed to list supported providers\n");
+        print_error_if_available();
+    }
+
+    // Static string: not freed
+    printf("   Library version: %s\n", tlsn_version());
+
+    // Extra headers are parsed before anything else runs; malformed lines are rejected
+    result = tlsn_prove(
+        MODE_PROVE,
+        "/test",
+        NULL,
+        NULL,
+        "X-Device-Id: test-device\nmissing colon",
+        test_user_agent,
+        test_provider_host,
+        test_provider_port,
+        test_notary_host,
+        test_notary_port,
+        test_notary_tls_enabled,
+        test_max_sent_data,
+        test_max_recv_data
+    );
+    if (result != 0) {
+        printf("   ✅ Malformed extra header rejected with code: %d\n", result);
+        print_error_if_available();
+    } else {
+        printf("   ❌ Malformed extra header should have been rejected\n");
+    }
+
     // Integration tests (only run if enabled and credentials are set)
     if (ENABLE_INTEGRATION_TESTS) {
         printf("\n=== INTEGRATION TESTS ===\n");
@@ -118,8 +156,15 @@ int main() {
             printf("\n⚠️  Integration tests skipped - credentials not set\n");
             printf("Set ZKP2P_TEST_URL, ZKP2P_TEST_COOKIE, and ZKP2P_TEST_ACCESS_TOKEN in .env\n");
         } else {
+            // Warm DNS and connection caches before the first proof
+            if (tlsn_warmup(test_provider_host, test_provider_port) == 0) {
+                printf("\n   ✅ Warmed up connection to %s\n", test_provider_host);
+            } else {
+                print_error_if_available();
+            }
+
             // Test prove-to-present operation (creates both attestation and presentation)
-            printf("\n4. Testing tlsn_prove() in PROVE_TO_PRESENT mode...\n");
+            printf("\n5. Testing tlsn_prove() in PROVE_TO_PRESENT mode...\n");
             printf("   Mode: PROVE_TO_PRESENT (%d)\n", MODE_PROVE_TO_PRESENT);
             printf("   URL: %s\n", test_url);
             printf("   Using credentials from environment variables...\n");
@@ -129,6 +174,7 @@ int main() {
                 test_url,
                 test_cookie,
                 test_access_token,
+                NULL,  // No extra headers
                 test_user_agent,
                 test_provider_host,
                 test_provider_port,
@@ -141,6 +187,11 @@ int main() {
 
             if (result == 0) {
                 printf("   ✅ Prove-to-present operation successful\n");
+                const char* paths = tlsn_last_output_paths();
+                if (paths) {
+                    printf("   Output files: %s\n", paths);
+                    tlsn_free_string((char*)paths);
+                }
             } else {
                 printf("   ⚠️  Prove-to-present operation failed with code: %d\n", result);
                 printf("   (This may be expected if notary server is not running)\n");
@@ -148,9 +199,9 @@ int main() {
             }
 
             // Test verify operation
-            printf("\n5. Testing tlsn_verify()...\n");
-            printf("   Verifying presentation file for wise.com...\n");
-            result = tlsn_verify("wise.com", test_unauthed_bytes);
+            printf("\n6. Testing tlsn_verify()...\n");
+            printf("   Verifying presentation file for %s...\n", test_url);
+            result = tlsn_verify(test_url, test_unauthed_bytes, NULL, NULL);
 
             if (result == 0) {
                 printf("   ✅ Verify operation successful\n");
@@ -161,12 +212,13 @@ int main() {
             }
 
             // Test pure prove operation
-            printf("\n6. Testing tlsn_prove() in PROVE mode...\n");
+            printf("\n7. Testing tlsn_prove() in PROVE mode...\n");
             result = tlsn_prove(
                 MODE_PROVE,
                 test_url,
                 test_cookie,
                 test_access_token,
+                NULL,  // No extra headers
                 test_user_agent,
                 test_provider_host,
                 test_provider_port,
@@ -186,12 +238,13 @@ int main() {
             }
 
             // Test pure present operation (uses existing attestation)
-            printf("\n7. Testing tlsn_prove() in PRESENT mode...\n");
+            printf("\n8. Testing tlsn_prove() in PRESENT mode...\n");
             result = tlsn_prove(
                 MODE_PRESENT,
-                NULL,  // URL not required for present mode
+                test_url,  // Selects the attestation saved for this transaction
                 NULL,  // Cookie not required for present mode
                 NULL,  // Access token not required for present mode
+                NULL,  // No extra headers
                 test_user_agent,
                 test_provider_host,
                 test_provider_port,
@@ -219,7 +272,7 @@ int main() {
     }
 
     // Cleanup
-    printf("\n8. Testing tlsn_cleanup()...\n");
+    printf("\n9. Testing tlsn_cleanup()...\n");
     tlsn_cleanup();
     printf("   ✅ Cleanup completed\n");
 